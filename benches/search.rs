@@ -0,0 +1,58 @@
+use std::hint::black_box;
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use simple_grep::{Config, search_ascii_case_insensitive, search_case_insensitive, search_reader};
+
+fn log_contents() -> String {
+    let lines = [
+        "2024-01-02T03:04:05Z INFO request served in 12ms",
+        "2024-01-02T03:04:06Z WARN cache miss for key user:42",
+        "2024-01-02T03:04:07Z ERROR upstream timed out after 30s",
+        "2024-01-02T03:04:08Z DEBUG connection pool size is 16",
+    ];
+
+    lines
+        .iter()
+        .cycle()
+        .take(10_000)
+        .fold(String::new(), |mut acc, line| {
+            acc.push_str(line);
+            acc.push('\n');
+            acc
+        })
+}
+
+fn case_insensitive(c: &mut Criterion) {
+    let contents = log_contents();
+    let mut group = c.benchmark_group("case_insensitive");
+
+    group.bench_function("unicode", |b| {
+        b.iter(|| search_case_insensitive(black_box("timed OUT"), black_box(&contents)))
+    });
+    group.bench_function("ascii", |b| {
+        b.iter(|| search_ascii_case_insensitive(black_box("timed OUT"), black_box(&contents)))
+    });
+
+    group.finish();
+}
+
+fn many_patterns(c: &mut Criterion) {
+    let contents = log_contents();
+    let config = Config {
+        patterns: (0..100).map(|i| format!("user:{i}")).collect(),
+        ..Config::default()
+    };
+
+    // With the `aho-corasick` feature, the lines are scanned for all the
+    // patterns at once.
+    c.bench_function("many_patterns", |b| {
+        b.iter(|| {
+            let mut out = Vec::new();
+            search_reader(&config, black_box(contents.as_bytes()), &mut out).unwrap();
+            out
+        })
+    });
+}
+
+criterion_group!(benches, case_insensitive, many_patterns);
+criterion_main!(benches);
@@ -0,0 +1,83 @@
+//! Searching the raw bytes of files for `--hex` and `--find-non-ascii`.
+
+use std::fmt::Write as _;
+
+/// Number of bytes shown on each side of a match.
+const SNIPPET_CONTEXT: usize = 4;
+
+/// Parses a hex byte sequence such as `DEADBEEF`, `0xdeadbeef` or
+/// `de ad be ef`.
+pub(crate) fn parse_hex(text: &str) -> Result<Vec<u8>, String> {
+    let digits: String = text.split_whitespace().collect();
+    let digits = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+        .unwrap_or(&digits);
+
+    let invalid = || format!("Invalid hex bytes '{text}': expected pairs of hex digits");
+
+    if digits.is_empty() || !digits.len().is_multiple_of(2) || !digits.is_ascii() {
+        return Err(invalid());
+    }
+
+    (0..digits.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&digits[i..i + 2], 16).map_err(|_| invalid()))
+        .collect()
+}
+
+/// Returns the offsets of the non-overlapping occurrences of `needle` in
+/// `haystack`, from left to right.
+pub(crate) fn find_all(haystack: &[u8], needle: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut start = 0;
+
+    while start + needle.len() <= haystack.len() {
+        if haystack[start..].starts_with(needle) {
+            offsets.push(start);
+            start += needle.len();
+        } else {
+            start += 1;
+        }
+    }
+
+    offsets
+}
+
+/// Renders the `len` bytes at `offset` in `bytes` and a few bytes around
+/// them as hex, with the match in brackets: `02 03 [de ad] 04 05`.
+pub(crate) fn snippet(bytes: &[u8], offset: usize, len: usize) -> String {
+    let start = offset.saturating_sub(SNIPPET_CONTEXT);
+    let end = (offset + len + SNIPPET_CONTEXT).min(bytes.len());
+    let mut snippet = String::new();
+
+    for (index, byte) in bytes[start..end].iter().enumerate() {
+        let index = start + index;
+
+        if index > start {
+            snippet.push(' ');
+        }
+        if index == offset {
+            snippet.push('[');
+        }
+
+        // Writing to a `String` can't fail.
+        let _ = write!(snippet, "{byte:02x}");
+
+        if index + 1 == offset + len {
+            snippet.push(']');
+        }
+    }
+
+    snippet
+}
+
+/// Returns the 1-based columns and values of the bytes of `line` that aren't
+/// ASCII, from left to right.
+pub(crate) fn non_ascii(line: &[u8]) -> Vec<(usize, u8)> {
+    line.iter()
+        .enumerate()
+        .filter(|(_, byte)| !byte.is_ascii())
+        .map(|(index, &byte)| (index + 1, byte))
+        .collect()
+}
@@ -0,0 +1,98 @@
+//! Stopping a search early, such as on Ctrl-C or after `--max-time`.
+
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// A flag that stops a search once set, shared by all its clones.
+///
+/// The search checks it before each file and each line, so the output
+/// written so far stays complete: [`run`](crate::run) stops, flushes it,
+/// resets the colors and returns. With the `ctrlc` feature, the binary sets
+/// the flag of its [`Config`](crate::Config) on Ctrl-C.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::CancelFlag;
+///
+/// let flag = CancelFlag::default();
+/// let handle = flag.clone();
+///
+/// assert!(!flag.is_cancelled());
+/// handle.cancel();
+/// assert!(flag.is_cancelled());
+/// ```
+#[derive(Debug, Default, Clone)]
+pub struct CancelFlag(Arc<AtomicBool>);
+
+impl CancelFlag {
+    /// Sets the flag, asking the search to stop.
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    /// Returns `true` once the flag has been set.
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+
+    /// Sets the flag once `budget` has elapsed, unless the returned
+    /// [`Deadline`] is dropped before. This is how `--max-time` stops the
+    /// search, the same way as Ctrl-C.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use simple_grep::CancelFlag;
+    ///
+    /// let flag = CancelFlag::default();
+    /// let deadline = flag.cancel_after(Duration::from_millis(10));
+    ///
+    /// std::thread::sleep(Duration::from_millis(200));
+    /// assert!(flag.is_cancelled());
+    /// assert!(deadline.expired());
+    /// ```
+    pub fn cancel_after(&self, budget: Duration) -> Deadline {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let expired = Arc::new(AtomicBool::new(false));
+        let (flag, timer_expired) = (self.clone(), Arc::clone(&expired));
+
+        thread::spawn(move || {
+            // Dropping the `Deadline` disconnects the channel, waking the
+            // timer up before the budget has elapsed.
+            if stopped.recv_timeout(budget) == Err(RecvTimeoutError::Timeout)
+                && !flag.is_cancelled()
+            {
+                timer_expired.store(true, Ordering::Relaxed);
+                flag.cancel();
+            }
+        });
+
+        Deadline {
+            _stop: stop,
+            expired,
+        }
+    }
+}
+
+/// A timer that sets a [`CancelFlag`] once it expires, returned by
+/// [`CancelFlag::cancel_after`]. Dropping it stops the timer.
+#[derive(Debug)]
+pub struct Deadline {
+    /// Disconnects the channel the timer waits on when dropped.
+    _stop: mpsc::Sender<()>,
+    expired: Arc<AtomicBool>,
+}
+
+impl Deadline {
+    /// Returns `true` if the timer expired and set the flag, rather than
+    /// the flag having been set some other way first.
+    pub fn expired(&self) -> bool {
+        self.expired.load(Ordering::Relaxed)
+    }
+}
@@ -0,0 +1,70 @@
+//! Reading the query from the system clipboard for `--pattern-clipboard`.
+
+/// Where `--pattern-clipboard` reads the query from: the
+/// [`SystemClipboard`], or a stand-in for it such as in tests.
+pub trait ClipboardSource {
+    /// Returns the text on the clipboard.
+    ///
+    /// # Errors
+    ///
+    /// Returns a description of the problem if the clipboard can't be read.
+    fn text(&mut self) -> Result<String, String>;
+}
+
+/// The system clipboard, read with the `arboard` crate. Reading it requires
+/// the `clipboard` feature, and fails without it.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClipboard;
+
+impl ClipboardSource for SystemClipboard {
+    #[cfg(feature = "clipboard")]
+    fn text(&mut self) -> Result<String, String> {
+        arboard::Clipboard::new()
+            .and_then(|mut clipboard| clipboard.get_text())
+            .map_err(|err| format!("Can't read the query from the clipboard: {err}"))
+    }
+
+    #[cfg(not(feature = "clipboard"))]
+    fn text(&mut self) -> Result<String, String> {
+        Err(String::from(
+            "--pattern-clipboard requires the `clipboard` feature",
+        ))
+    }
+}
+
+/// Reads the query for `--pattern-clipboard` from `source`, without the line
+/// break at the end that text copied from a terminal often has.
+///
+/// # Errors
+///
+/// Returns an error if the clipboard can't be read or holds no text.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::{ClipboardSource, clipboard_query};
+///
+/// struct Copied(&'static str);
+///
+/// impl ClipboardSource for Copied {
+///     fn text(&mut self) -> Result<String, String> {
+///         Ok(self.0.to_string())
+///     }
+/// }
+///
+/// assert_eq!(clipboard_query(&mut Copied("disk full\n")).unwrap(), "disk full");
+/// assert!(clipboard_query(&mut Copied("")).is_err());
+/// ```
+pub fn clipboard_query(source: &mut dyn ClipboardSource) -> Result<String, String> {
+    let text = source.text()?;
+    let query = text.strip_suffix('\n').unwrap_or(&text);
+    let query = query.strip_suffix('\r').unwrap_or(query);
+
+    if query.is_empty() {
+        return Err(String::from(
+            "The clipboard is empty, there is no query to search for",
+        ));
+    }
+
+    Ok(query.to_string())
+}
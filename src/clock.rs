@@ -0,0 +1,80 @@
+//! The time at which matches are found, for `--timestamp`.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Tells the time for `--timestamp`: the time of the system, or a fixed
+/// time so that the output can be reproduced, such as in tests.
+///
+/// # Examples
+///
+/// ```
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// use simple_grep::Clock;
+///
+/// let time = UNIX_EPOCH + Duration::from_secs(1_704_164_645);
+/// let clock = Clock::fixed(time);
+///
+/// assert_eq!(clock.now(), time);
+/// assert_eq!(clock.rfc3339(), "2024-01-02T03:04:05Z");
+/// ```
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Clock {
+    /// The time always told, or `None` for the time of the system.
+    fixed: Option<SystemTime>,
+}
+
+impl Clock {
+    /// A clock that always tells `time`.
+    pub fn fixed(time: SystemTime) -> Clock {
+        Clock { fixed: Some(time) }
+    }
+
+    /// Returns the current time.
+    pub fn now(&self) -> SystemTime {
+        self.fixed.unwrap_or_else(SystemTime::now)
+    }
+
+    /// Returns the current time in UTC as an RFC 3339 timestamp, to the
+    /// second, like `2024-01-02T03:04:05Z`. Times before 1970 are told as
+    /// 1970-01-01T00:00:00Z.
+    pub fn rfc3339(&self) -> String {
+        let seconds = self
+            .now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_secs());
+        let (days, time) = (seconds / 86_400, seconds % 86_400);
+        let (year, month, day) = civil_date(days);
+
+        format!(
+            "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+            time / 3600,
+            time % 3600 / 60,
+            time % 60
+        )
+    }
+}
+
+/// Returns the year, month and day of the date `days` days after
+/// 1970-01-01 in the Gregorian calendar, with Howard Hinnant's
+/// `civil_from_days` algorithm.
+fn civil_date(days: u64) -> (u64, u64, u64) {
+    // Count from 0000-03-01, so that leap days come last in each 400-year
+    // era and in each year.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + u64::from(month <= 2);
+
+    (year, month, day)
+}
@@ -0,0 +1,1997 @@
+//! Command-line argument parsing.
+
+use std::env;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::binary::parse_hex;
+use crate::input::{DEFAULT_BUFFER_SIZE, MAX_BUFFER_SIZE};
+use crate::{CancelFlag, Clock, ColorChoice, Normalization, OutputEncoding, Template};
+
+/// Name of the environment variable holding default command-line options.
+///
+/// Its contents are split on whitespace and parsed before the actual
+/// command-line arguments, so options given on the command line take
+/// precedence, e.g. `MINIGREP_OPTIONS="-i -n"`.
+pub const OPTIONS_ENV: &str = "MINIGREP_OPTIONS";
+
+/// Holds the configuration parameters for the SimpleGrep application.
+///
+/// This struct is created using the [`Config::build`] method,
+/// which parses command-line arguments and reads the `IGNORE_CASE`
+/// environment variable to determine if the search should be case-insensitive.
+#[derive(Debug, Default)]
+pub struct Config {
+    /// Print the usage summary, see [`help`](crate::help), instead of
+    /// searching (`--help`).
+    pub help: bool,
+
+    /// Print every option in a machine-readable form, see
+    /// [`list_options`](crate::list_options), instead of searching
+    /// (`--list-options`).
+    pub list_options: bool,
+
+    /// Print the version and the compiled-in features instead of searching
+    /// (`--version`), see [`version`](crate::version). No query or file path
+    /// is needed then, nor with `help` and `list_options`.
+    pub version: bool,
+
+    /// The string to search for in the file.
+    pub query: String,
+
+    /// The queries given with `-e` or `--regexp`, in order, instead of
+    /// `query`. A line matches if it matches any of them, see
+    /// [`queries`](Config::queries).
+    pub patterns: Vec<String>,
+
+    /// The paths of the input files (or directories, with `recursive`) to search.
+    /// A path of `-` stands for standard input.
+    pub file_paths: Vec<String>,
+
+    /// A flag that determines whether the search should be case-insensitive.
+    /// This is set by the `-i` option or the `IGNORE_CASE` environment variable.
+    pub ignore_case: bool,
+
+    /// Fold case using ASCII rules only when `ignore_case` is set
+    /// (`--ascii-case`, which also enables `ignore_case`).
+    ///
+    /// This avoids allocating a lowercase copy of every line, but letters
+    /// outside ASCII only match if their case is identical.
+    pub ascii_case: bool,
+
+    /// Match the query as it is, lowercased, uppercased or in title case,
+    /// each case-sensitively (`--case-variants`), rather than folding the
+    /// case of every line. Has no effect with `ignore_case`, `regex` or
+    /// `fuzzy`.
+    pub case_variants: bool,
+
+    /// Search the raw bytes of the files for this byte sequence instead of
+    /// searching lines for the query (`--hex`, such as `--hex DEADBEEF`).
+    /// No query is given then. Each match is printed as its byte offset
+    /// followed by a hex dump of the bytes around it.
+    pub hex: Option<Vec<u8>>,
+
+    /// Instead of searching for a query, report the lines of the files with
+    /// bytes that aren't ASCII, with the column and value of each of those
+    /// bytes (`--find-non-ascii`). The files needn't be valid UTF-8.
+    pub find_non_ascii: bool,
+
+    /// Fail the search on the first file that isn't valid UTF-8, even one
+    /// found recursively, with the byte offset of its first invalid byte
+    /// (`--strict-utf8`), so that malformed files can be rejected.
+    pub strict_utf8: bool,
+
+    /// Take the query from the system clipboard instead of the arguments
+    /// (`--pattern-clipboard`), to search for long patterns without escaping
+    /// them for the shell. [`Config::build`] leaves `query` empty; the binary
+    /// reads it with [`clipboard_query`](crate::clipboard_query). Requires the
+    /// `clipboard` feature.
+    pub pattern_clipboard: bool,
+
+    /// Normalize the query and the input to this Unicode normalization form
+    /// before matching (`--normalize NFC|NFD`), so that composed and
+    /// decomposed characters match each other. Lines are printed, and byte
+    /// offsets counted, in the normalized form. Requires the `normalize`
+    /// feature.
+    pub normalize: Option<Normalization>,
+
+    /// Interpret the query as a regular expression (`-E` or `--regex`), see
+    /// `RegexMatcher`. Requires the `regex` feature.
+    pub regex: bool,
+
+    /// With `regex`, skip the lines that take longer than this to match,
+    /// with a warning on stderr (`--regex-timeout MS`). The regex engine runs
+    /// in time linear in the length of a line, so matching can't hang on
+    /// catastrophic backtracking, but a complex pattern can still be slow on
+    /// long lines. Matching isn't interrupted: the line is skipped once it's
+    /// done.
+    pub regex_timeout: Option<Duration>,
+
+    /// Match the regular expression against the whole contents of each file
+    /// rather than line by line, so matches can span several lines
+    /// (`-U` or `--multiline`, which also enables `regex`). Each match is
+    /// printed in full, prefixed with the line it starts on.
+    pub multiline: bool,
+
+    /// Let `.` match line breaks with `multiline` (`--multiline-dotall`,
+    /// which also enables `multiline`).
+    pub multiline_dotall: bool,
+
+    /// With `multiline`, print the line breaks inside each match as `\n`
+    /// (`--flatten-matches`), so every match takes a single line of output.
+    pub flatten_matches: bool,
+
+    /// Only check whether the regular expression matches the whole contents
+    /// of each file, printing nothing (`--whole-file-match`, which also
+    /// enables `regex`). `^` and `$` match at the start and end of the file,
+    /// like `\A` and `\z`. Each file counts as a single selected line if it
+    /// matches.
+    pub whole_file_match: bool,
+
+    /// Search the whole contents of each file as a single string, so the
+    /// query can span line breaks, printing the file name and a snippet
+    /// around each match (`--slurp`). Unlike `multiline`, the query is
+    /// literal unless `regex` is set.
+    pub slurp: bool,
+
+    /// Match lines containing a substring within this Levenshtein distance of
+    /// the query, see [`FuzzyMatcher`](crate::FuzzyMatcher). Set by
+    /// `--fuzzy` (distance 1) and `--max-distance N`.
+    pub fuzzy: Option<usize>,
+
+    /// Collapse each run of whitespace in the queries and the lines to a
+    /// single space before matching (`--squeeze-whitespace`), so lines that
+    /// only differ in spacing match. The lines are printed as they are.
+    pub squeeze_whitespace: bool,
+
+    /// Only match whole words (`-w` or `--word-regexp`): a match must start
+    /// and end at word boundaries, words being runs of alphanumeric
+    /// characters and `_`.
+    pub word_regexp: bool,
+
+    /// Only match at the start of words (`--word-start`), so `cat` matches
+    /// `category` but not `concatenate`. Unlike `word_regexp`, the match may
+    /// end inside a word.
+    pub word_start: bool,
+
+    /// Only count the matches that are at least this many characters long
+    /// (`--min-match-len N`), so that a line only matches if one of them
+    /// is. This drops short accidental matches of `regex` and `fuzzy`.
+    pub min_match_len: usize,
+
+    /// Only search the lines from one containing this pattern up to the next
+    /// one containing `to`, inclusive (`--from`). Each such range of a file
+    /// is searched; without `from`, searching starts at the first line.
+    pub from: Option<String>,
+
+    /// Stop searching at the next line containing this pattern, inclusive,
+    /// until `from` matches again (`--to`). Without `to`, a range lasts
+    /// until the end of the file.
+    pub to: Option<String>,
+
+    /// Parse each line as a JSON object and match the queries, and the
+    /// `also_matching` and `exclude_matching` patterns, against its string
+    /// values only, not its keys (`--json-input`). Matching lines are
+    /// printed as they are. Requires the `json` feature.
+    pub json_input: bool,
+
+    /// With `json_input`, match lines that aren't JSON objects as plain text
+    /// instead of skipping them (`--match-invalid-json`).
+    pub match_invalid_json: bool,
+
+    /// Only keep the selected lines that also match this pattern, to require
+    /// two patterns on the same line (`--also-matching`). Like the query, it
+    /// honors `ignore_case`; it is a regular expression with `regex` and a
+    /// literal otherwise.
+    pub also_matching: Option<String>,
+
+    /// Drop the selected lines that also match this pattern, like piping the
+    /// output through `grep -v` (`--exclude-matching`). The pattern is a
+    /// regular expression with `regex` and a literal otherwise.
+    pub exclude_matching: Option<String>,
+
+    /// Remove a UTF-8 byte order mark from the start of each file before
+    /// searching it (`--strip-bom`), so the first line can match a query
+    /// anchored at its start. Byte offsets are still those in the file, so
+    /// the offset of the first line is 3 instead of 0 if a mark was removed.
+    pub strip_bom: bool,
+
+    /// Select the lines that do **not** contain the query (`-v`).
+    pub invert_match: bool,
+
+    /// Prefix each matching line with its 1-based line number (`-n`).
+    pub line_number: bool,
+
+    /// Number of lines to print after each selected line (`-A`, or `-C` for
+    /// both directions). Groups of lines that aren't adjacent are separated
+    /// by `--`.
+    pub after_context: usize,
+
+    /// Number of lines to print before each selected line (`-B`, or `-C`).
+    pub before_context: usize,
+
+    /// Print only the matching parts of each line, one per output line (`-o`).
+    pub only_matching: bool,
+
+    /// Report matches that touch, where one ends and the next one starts, as
+    /// a single match, for `only_matching` and highlighting
+    /// (`--merge-adjacent`).
+    pub merge_adjacent: bool,
+
+    /// Report at most this many matches of each line with `only_matching`
+    /// (`--max-matches-per-line`). Whether a line matches is unaffected.
+    pub max_matches_per_line: Option<usize>,
+
+    /// Prefix each output line with the 0-based byte offset in the file of
+    /// the line, or of the match itself with `only_matching` (`-b`).
+    pub byte_offset: bool,
+
+    /// With `byte_offset` and `only_matching`, count the offset of each match
+    /// from the start of its line rather than of the file
+    /// (`--offset-relative`).
+    pub offset_relative: bool,
+
+    /// Search directories recursively (`-r`).
+    pub recursive: bool,
+
+    /// Follow symbolic links while searching recursively (`--follow`).
+    /// Without it, links are skipped.
+    pub follow: bool,
+
+    /// Search the output of running this command with the path of each file
+    /// as its last argument instead of the file itself (`--pre COMMAND`),
+    /// such as a script that extracts the text of PDFs. The command is split
+    /// on whitespace into a program and its arguments, and reads standard
+    /// input for `-`. Files for which the command fails are skipped with a
+    /// warning.
+    pub pre: Option<String>,
+
+    /// Skip the directories whose name matches any of these globs while
+    /// searching recursively, without reading them (`--exclude-dir GLOB`,
+    /// which may be repeated). `*` matches any characters and `?` a single
+    /// one.
+    pub exclude_dir: Vec<String>,
+
+    /// Only search the files of these types while searching recursively
+    /// (`--type NAME`, which may be repeated), such as `rust` for `*.rs`
+    /// files. Files named explicitly are always searched.
+    pub file_types: Vec<String>,
+
+    /// File types added to the built-in ones, or globs added to one of
+    /// them, as `NAME:GLOB` (`--type-add`, which may be repeated).
+    pub type_add: Vec<String>,
+
+    /// Match the globs of `exclude_dir` and `file_types` regardless of case
+    /// (`--glob-ignore-case`), so that `*.txt` matches `NOTES.TXT` too.
+    pub glob_ignore_case: bool,
+
+    /// Only search the first this many files (`--max-files N`), in the order
+    /// of the `file_paths` and, within directories, of their names.
+    pub max_files: Option<usize>,
+
+    /// Search the files in the reverse order (`--reverse-file-order`): the
+    /// last of the `file_paths` first and, within directories, by
+    /// descending name. It applies after `max_files`.
+    pub reverse_file_order: bool,
+
+    /// Start searching each file at this byte offset (`--seek-bytes N`), for
+    /// resuming a search. The line the offset falls into is skipped unless
+    /// it starts there or `include_partial` is set. Reported byte offsets
+    /// are still counted from the start of the file, but line numbers are
+    /// counted from the offset.
+    pub seek_bytes: u64,
+
+    /// With `seek_bytes`, search the rest of the line the offset falls into
+    /// too (`--include-partial`).
+    pub include_partial: bool,
+
+    /// Read the paths to search from standard input, one per line, in
+    /// addition to `file_paths` (`--stdin-paths`), like `find . | xargs grep`.
+    /// With it, `file_paths` may be empty.
+    pub stdin_paths: bool,
+
+    /// Search this file instead, printing only its selected lines that
+    /// aren't also selected lines of the `file_paths` (`--only-in FILE`),
+    /// compared ignoring case with `ignore_case`.
+    pub only_in: Option<String>,
+
+    /// With `only_in`, then also print the selected lines of the
+    /// `file_paths` that aren't selected lines of that file (`--symmetric`).
+    pub symmetric: bool,
+
+    /// With `stdin_paths`, the paths are separated by NUL bytes instead of
+    /// line breaks (`-Z`, `--null`), as printed by `find -print0`.
+    pub null: bool,
+
+    /// Decompress standard input when it is compressed with gzip, bzip2 or
+    /// xz (`-z`, `--decompress-stdin`), as detected from its first bytes
+    /// like for files. Standard input is otherwise searched as it is,
+    /// without waiting for its first bytes to tell.
+    pub decompress_stdin: bool,
+
+    /// Render each match with a custom template instead of the default
+    /// output format (`--format`), see [`Template`].
+    pub format: Option<Template>,
+
+    /// Size in bytes of the buffer input files are read with
+    /// (`--buffer-size`, which accepts `K`, `M` and `G` suffixes), see
+    /// [`Config::buffer_capacity`].
+    pub buffer_size: Option<usize>,
+
+    /// Search this many files at a time on separate threads (`-j N` or
+    /// `--threads N`). The output is the same as with a single thread. It is
+    /// ignored with `sample`, `max_total`, `line_buffered` and
+    /// `continuous_lineno`.
+    pub threads: Option<usize>,
+
+    /// Number the lines of all files on from the last line of the file
+    /// before, as if they were one concatenated file
+    /// (`--continuous-lineno`), rather than from 1 in each file. The files
+    /// are then searched on a single thread.
+    pub continuous_lineno: bool,
+
+    /// Search the files one after another instead, splitting each large
+    /// file into parts of whole lines that are searched on the `threads`
+    /// (`--parallel-within-file`), for when a single file is huge. Files are
+    /// searched on a single thread when context, ranges, `skip` or the
+    /// options that collect all lines of a file are used.
+    pub parallel_within_file: bool,
+
+    /// Read the input line by line and flush each match as soon as it is
+    /// found (`--line-buffered`), for following input that keeps growing.
+    pub line_buffered: bool,
+
+    /// Prefix each printed match with the time it was found, in UTC as
+    /// told by the `clock` (`--timestamp`), like `[2024-01-02T03:04:05Z]
+    /// line`. The `format`, `csv` and `json` output has none.
+    pub timestamp: bool,
+
+    /// Truncate printed lines to this many characters, marking the cut with
+    /// `…` (`--max-columns`). Matching still looks at the whole line.
+    pub max_columns: Option<usize>,
+
+    /// The encoding of everything written to stdout (`--output-encoding`),
+    /// UTF-8 by default.
+    pub output_encoding: OutputEncoding,
+
+    /// End each printed line that ended with `\r\n` in its file with `\r\n`
+    /// too rather than `\n` (`--respect-crlf`), so CRLF files round-trip
+    /// with `--passthru`.
+    pub respect_crlf: bool,
+
+    /// Leave out the line break after the last line of the output
+    /// (`--no-trailing-newline`), such as to capture a single match in a
+    /// shell variable.
+    pub no_trailing_newline: bool,
+
+    /// Write the output of each file to a file of its own below this
+    /// directory instead of stdout (`--split-output DIR`), see
+    /// [`split_output_path`](crate::split_output_path).
+    pub split_output: Option<String>,
+
+    /// Write the selected lines matching each query, such as each `-e`
+    /// pattern, to a file of its own in this directory instead of stdout
+    /// (`--split-by-pattern DIR`): `DIR/1.txt` for the first, and so on. A
+    /// line matching several queries is written to the file of each.
+    pub split_by_pattern: Option<String>,
+
+    /// Write the output to this file instead of stdout (`--output FILE`),
+    /// replacing its contents unless `append` is set.
+    pub output: Option<String>,
+
+    /// Show the output in this pager when stdout is a terminal
+    /// (`--pager CMD`), instead of `less -R`. The command is split on
+    /// whitespace. Quitting the pager early stops the search.
+    pub pager: Option<String>,
+
+    /// Never show the output in a pager (`--no-pager`).
+    pub no_pager: bool,
+
+    /// Compress the `output` file with gzip (`--compress`). With `append`,
+    /// each run adds a gzip member, which decompress as one stream. Requires
+    /// the `gzip` feature.
+    pub compress: bool,
+
+    /// Append to the `output` file, and to the files of `split_output` and
+    /// `split_by_pattern`, instead of replacing their contents (`--append`),
+    /// to accumulate the output of several runs.
+    pub append: bool,
+
+    /// Stops the search once cancelled, see [`CancelFlag`]. It can't be set
+    /// with an option.
+    pub cancel: CancelFlag,
+
+    /// Tells the time for `timestamp`, see [`Clock`]. It can't be set with
+    /// an option.
+    pub clock: Clock,
+
+    /// Stop the search once this much time has elapsed (`--max-time SECS`),
+    /// like when it's cancelled, with a note on stderr that the results are
+    /// incomplete. The time is checked before each file and line.
+    pub max_time: Option<Duration>,
+
+    /// With `split_output`, don't create output files for files without any
+    /// output (`--skip-empty`).
+    pub skip_empty: bool,
+
+    /// When to highlight matches with colors (`--color`).
+    pub color: ColorChoice,
+
+    /// Highlight matches by surrounding them with `>>` and `<<` (`--mark`),
+    /// for when colors aren't available.
+    pub mark: bool,
+
+    /// With colors, show the matches normally and dim the rest of the line
+    /// instead of highlighting the matches (`--dim-context`).
+    pub dim_context: bool,
+
+    /// Show the control characters of the printed lines as visible escapes
+    /// like `cat -v`, such as `^[` and `^M` (`--escape-nonprinting`), so
+    /// they can't mangle the terminal. Matching is unaffected.
+    pub escape_nonprinting: bool,
+
+    /// Print every line, not just the matching ones, highlighting the
+    /// matches (`--passthru`).
+    pub passthru: bool,
+
+    /// Print every line of each file that has at least one selected line,
+    /// highlighting the matches like `passthru`, and nothing of the other
+    /// files (`--print-whole-file`).
+    pub print_whole_file: bool,
+
+    /// Only search the files in which each of the queries, such as several
+    /// `-e` patterns, matches at least one selected line, not necessarily
+    /// the same one, and print nothing of the other files (`--file-all`).
+    pub file_all: bool,
+
+    /// Instead of the lines, print `path: selected/total (percent)` for each
+    /// file (`--ratio`). With `invert_match`, non-matching lines are counted.
+    pub ratio: bool,
+
+    /// Instead of the lines, print how clustered the selected lines of each
+    /// file are (`--density`): `path: N matches per 100 lines, longest run
+    /// N`, where the longest run is the most selected lines in a row.
+    pub density: bool,
+
+    /// Instead of the lines, print the number of lines and bytes of each file
+    /// and how many of them were selected (`--summary`), followed by the same
+    /// for all files. The bytes of the selected lines don't include their
+    /// line breaks.
+    pub summary: bool,
+
+    /// After searching, print a JSON object with the number of files
+    /// searched and matched, of selected lines and of milliseconds taken to
+    /// standard error (`--summary-json`), apart from the matches.
+    pub summary_json: bool,
+
+    /// Skip the files whose contents are the same as those of a file found
+    /// before (`--skip-duplicate-content`), noting each on stderr. Unlike
+    /// `dedupe_files`, this compares a hash of the contents, not the paths.
+    pub skip_duplicate_content: bool,
+
+    /// Note each zero-byte file among those to search on stderr
+    /// (`--report-empty`). Empty files are searched like any other: they
+    /// have no lines, so nothing is selected in them, even with `-v`, and
+    /// they count 0 with `count`.
+    pub report_empty: bool,
+
+    /// Search each file only once, even if it is reached through several
+    /// arguments or links (`--dedupe-files`). Files are compared by their
+    /// canonical path.
+    pub dedupe_files: bool,
+
+    /// Skip files that can't be read without a message, even those given
+    /// explicitly, and exit with status 2 afterwards (`--quiet-errors`).
+    pub quiet_errors: bool,
+
+    /// Fail the search on the first file that can't be searched, even one
+    /// found recursively, instead of skipping it with a warning
+    /// (`--fail-fast`). This takes precedence over `quiet_errors`.
+    pub fail_fast: bool,
+
+    /// Print each match as a `path,line_number,text` CSV row (`--csv`).
+    pub csv: bool,
+
+    /// Write a `path,line_number,text` header row before the `--csv`
+    /// output (`--csv-header`, which also enables `csv`).
+    pub csv_header: bool,
+
+    /// Instead of the lines, print the number of selected lines of each
+    /// file (`-c`).
+    pub count: bool,
+
+    /// Instead of the lines, print `path (count)` for each file with at least
+    /// one selected line (`--files-with-counts`).
+    pub files_with_counts: bool,
+
+    /// After the output of all files, print `total:N` with the number of
+    /// selected lines across all of them (`--count-total`).
+    pub count_total: bool,
+
+    /// Stop the whole search after this many selected lines, counted across
+    /// all files in the order they are searched (`--max-total N`). Files
+    /// after the last match aren't opened.
+    pub max_total: Option<usize>,
+
+    /// Drop the first this many lines that would be selected in each file
+    /// (`--skip N`), such as a header line, so they're neither printed nor
+    /// counted.
+    pub skip: usize,
+
+    /// Handle the lines of each file from the last one to the first
+    /// (`--bottom-up`), such as to see the newest entries of a log first.
+    /// Lines keep their numbers from the top of the file, and context
+    /// follows this order too, so "before" context is the lines below.
+    /// Reads whole files, even with `line_buffered`.
+    pub bottom_up: bool,
+
+    /// Instead of the lines, print how many selected lines of each file match
+    /// each of the queries (`--count-by-pattern`), like `p1: 12, p2: 3`. A
+    /// line matching several queries is counted for each of them.
+    pub count_by_pattern: bool,
+
+    /// Instead of the lines, print for each of the queries how many of the
+    /// searched files have a selected line matching it, as `pattern:
+    /// files_matched/total_files` once all files are searched
+    /// (`--pattern-file-coverage`).
+    pub pattern_file_coverage: bool,
+
+    /// Instead of the lines, print for each pair of queries how many
+    /// selected lines match both, as `pattern & pattern: N` once all files
+    /// are searched (`--cooccurrence`).
+    pub cooccurrence: bool,
+
+    /// After searching, print the queries that no selected line matched to
+    /// stderr (`--report-unmatched`), to catch typos in `-e` patterns.
+    pub report_unmatched: bool,
+
+    /// Prefix each printed line with the labels of the queries it matches
+    /// (`--label-patterns`), like `[p1,p3] line`. A query of the form
+    /// `name=pattern` is labeled `name`, and any other with its own text.
+    /// `format`, `csv` and `json` output isn't labeled.
+    pub label_patterns: bool,
+
+    /// Prefix each printed line, or match with `only_matching`, with its
+    /// length in characters (`--char-count`), like `(42 chars) line`. The
+    /// length counts Unicode scalar values, not bytes, and is that of the
+    /// whole line even with `max_columns`.
+    pub char_count: bool,
+
+    /// Prefix each matching line with the columns of its first match, or
+    /// each match with its own with `-o`, as `start-end:` after the line
+    /// number (`--columns`, which also enables `line_number`). Columns are
+    /// 1-based positions in characters, and the end is inclusive.
+    pub columns: bool,
+
+    /// Instead of the lines, print the numbers of the selected lines of each
+    /// file with at least one, comma-separated on a single line
+    /// (`--numbers-only`), like `3,7,12`.
+    pub numbers_only: bool,
+
+    /// Instead of the lines, print the differences between the numbers of
+    /// successive selected lines of each file with at least two, and the
+    /// largest of them (`--gaps`), like `gaps 4,1,10 (max 10)`, to spot
+    /// where matches pause.
+    pub gaps: bool,
+
+    /// Instead of the lines, print the position of the first match of each
+    /// file with one as `path:lineno:col` and stop searching the file there
+    /// (`--first-only`). The column counts characters from 1.
+    pub first_only: bool,
+
+    /// After searching, print the number of bytes read from all files per
+    /// second of searching to standard error (`--measure-throughput`).
+    pub measure_throughput: bool,
+
+    /// Print only this many matches, chosen at random among all matches of
+    /// all files (`--sample N`). They are printed in the order they were found.
+    pub sample: Option<usize>,
+
+    /// Seed for choosing the `sample`, to get the same sample every time
+    /// (`--seed N`).
+    pub seed: Option<u64>,
+
+    /// Print each match as a JSON object with `path`, `line_number` and
+    /// `text` fields, one per line (`--json` or `--json-lines`). Files that
+    /// can't be read are reported on stderr as JSON objects too, see
+    /// `FileError::to_json`.
+    pub json: bool,
+
+    /// Print the `--json` objects as the elements of a single JSON array
+    /// (`--json-array`, which also enables `json`). It can't be combined with
+    /// the options that print something else, like `count` or `summary`.
+    pub json_array: bool,
+
+    /// Print each distinct matching line once, preceded by how many times
+    /// it occurred (`--group-count`), most frequent first.
+    pub group_count: bool,
+
+    /// Instead of the lines, print a histogram of the lengths in characters
+    /// of the selected lines of all files once all have been searched
+    /// (`--length-histogram`).
+    pub length_histogram: bool,
+
+    /// The width of the buckets of the `length_histogram`
+    /// (`--bucket-size N`), 10 by default.
+    pub bucket_size: Option<usize>,
+
+    /// Instead of the lines, print this percentile of the lengths in
+    /// characters of the selected lines of each file with any, and of all
+    /// files (`--percentile P`), like `path: p95 line length 42`. It is
+    /// the smallest length that at least `P` percent of the lines have at
+    /// most.
+    pub percentile: Option<f64>,
+
+    /// Print the selected lines of each file sorted lexicographically rather
+    /// than in file order (`--sort-lines`), each with its own line number
+    /// and offset. Lines that are equal stay in file order. No context is
+    /// printed.
+    pub sort_lines: bool,
+
+    /// Instead of the lines, print only the longest selected line of each
+    /// file, in characters, with its line number (`--longest`). Of several
+    /// lines of the same length, the first one is printed.
+    pub longest: bool,
+
+    /// Sort in descending order with `sort_lines` (`--reverse`).
+    pub reverse: bool,
+}
+
+impl Config {
+    /// Parses command-line arguments and builds a `Config`.
+    ///
+    /// Options may appear anywhere among the positional arguments; a lone `--`
+    /// ends option parsing. Default options are read from the
+    /// [`OPTIONS_ENV`] environment variable first.
+    ///
+    /// # Arguments
+    ///
+    /// * `args` - An iterator over command-line arguments, typically from `env::args()`.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Config)` if a query (unless `--hex` or `--find-non-ascii` is
+    ///   given) and at least one
+    ///   file path are provided, or
+    ///   `--help`, `--list-options` or `--version` is given.
+    /// * `Err(String)` with an error message if arguments are missing or invalid.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use simple_grep::Config;
+    ///
+    /// let args = vec![
+    ///     String::from("simple_grep"), // normally the binary name
+    ///     String::from("-n"),
+    ///     String::from("Rust"),
+    ///     String::from("input.txt"),
+    /// ];
+    ///
+    /// let config = Config::build(args.into_iter()).unwrap();
+    ///
+    /// assert_eq!(config.query, "Rust");
+    /// assert_eq!(config.file_paths, ["input.txt"]);
+    /// assert!(config.line_number);
+    /// ```
+    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, String> {
+        args.next(); // skip program name
+
+        let mut config = Config {
+            ignore_case: env::var("IGNORE_CASE").is_ok(),
+            ..Config::default()
+        };
+
+        if let Ok(options) = env::var(OPTIONS_ENV) {
+            let mut options = options.split_whitespace().map(String::from);
+
+            while let Some(option) = options.next() {
+                if !is_option(&option) {
+                    return Err(format!(
+                        "{OPTIONS_ENV} may only contain options, found '{option}'"
+                    ));
+                }
+                config
+                    .parse_option(&option, &mut options)
+                    .map_err(|err| format!("{err} (in {OPTIONS_ENV})"))?;
+            }
+        }
+
+        let mut positional = Vec::new();
+        let mut options_done = false;
+
+        while let Some(arg) = args.next() {
+            if !options_done && arg == "--" {
+                options_done = true;
+            } else if !options_done && is_option(&arg) {
+                config.parse_option(&arg, &mut args)?;
+            } else {
+                positional.push(arg);
+            }
+        }
+
+        if config.help || config.list_options || config.version {
+            return Ok(config);
+        }
+
+        let mut positional = positional.into_iter();
+
+        if config.hex.is_none()
+            && !config.find_non_ascii
+            && !config.pattern_clipboard
+            && config.patterns.is_empty()
+        {
+            config.query = match positional.next() {
+                Some(arg) => arg,
+                None => return Err(String::from("Didn't get a query string")),
+            };
+        }
+
+        config.file_paths = positional.collect();
+
+        if config.file_paths.is_empty() && !config.stdin_paths {
+            return Err(String::from("Didn't get a file path"));
+        }
+
+        if let Some(option) = config.json_array_conflict() {
+            return Err(format!(
+                "--json-array can't be combined with {option}, whose output isn't JSON"
+            ));
+        }
+
+        Ok(config)
+    }
+
+    /// Returns an option set along with `json_array` whose output isn't made
+    /// of `--json` objects, so that it would break the array, if any.
+    fn json_array_conflict(&self) -> Option<&'static str> {
+        if !self.json_array {
+            return None;
+        }
+
+        [
+            (self.count, "--count"),
+            (self.count_total, "--count-total"),
+            (self.files_with_counts, "--files-with-counts"),
+            (self.count_by_pattern, "--count-by-pattern"),
+            (self.pattern_file_coverage, "--pattern-file-coverage"),
+            (self.cooccurrence, "--cooccurrence"),
+            (self.group_count, "--group-count"),
+            (self.summary, "--summary"),
+            (self.ratio, "--ratio"),
+            (self.density, "--density"),
+            (self.percentile.is_some(), "--percentile"),
+            (self.length_histogram, "--length-histogram"),
+            (self.numbers_only, "--numbers-only"),
+            (self.gaps, "--gaps"),
+            (self.longest, "--longest"),
+            (self.first_only, "--first-only"),
+            (self.hex.is_some(), "--hex"),
+            (self.find_non_ascii, "--find-non-ascii"),
+            (self.multiline, "--multiline"),
+            (self.slurp, "--slurp"),
+        ]
+        .into_iter()
+        .find(|&(set, _)| set)
+        .map(|(_, option)| option)
+    }
+
+    /// Returns the capacity of the buffer input files are read with: the
+    /// `buffer_size`, or 8 KiB by default.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use simple_grep::Config;
+    ///
+    /// let args = ["simple_grep", "--buffer-size", "64K", "Rust", "input.txt"];
+    /// let config = Config::build(args.into_iter().map(String::from)).unwrap();
+    ///
+    /// assert_eq!(config.buffer_capacity(), 64 * 1024);
+    /// assert_eq!(Config::default().buffer_capacity(), 8 * 1024);
+    /// ```
+    pub fn buffer_capacity(&self) -> usize {
+        self.buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Returns the queries to search for: the `patterns` given with `-e`, or
+    /// else the `query`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use simple_grep::Config;
+    ///
+    /// let args = ["simple_grep", "-e", "Rust", "-e", "Go", "input.txt"];
+    /// let config = Config::build(args.into_iter().map(String::from)).unwrap();
+    ///
+    /// assert_eq!(config.queries(), ["Rust", "Go"]);
+    /// assert_eq!(config.file_paths, ["input.txt"]);
+    /// ```
+    pub fn queries(&self) -> &[String] {
+        if self.patterns.is_empty() {
+            std::slice::from_ref(&self.query)
+        } else {
+            &self.patterns
+        }
+    }
+
+    /// Returns `true` if the query and the file paths look like they were
+    /// given in the wrong order, as in `simple_grep input.txt Rust`: the
+    /// query names an existing file, but none of the file paths exist.
+    pub(crate) fn arguments_look_swapped(&self) -> bool {
+        self.patterns.is_empty()
+            && !self.query.is_empty()
+            && Path::new(&self.query).is_file()
+            && !self.file_paths.is_empty()
+            && self
+                .file_paths
+                .iter()
+                .all(|path| path != "-" && !Path::new(path).exists())
+    }
+
+    /// Applies a single option such as `-i`, `-in`, `-C2`, `--line-number`
+    /// or `--format=TEMPLATE`.
+    ///
+    /// Options that take a value and weren't given one with `=` consume the
+    /// next argument from `args`.
+    fn parse_option(
+        &mut self,
+        arg: &str,
+        args: &mut dyn Iterator<Item = String>,
+    ) -> Result<(), String> {
+        let Some(long) = arg.strip_prefix("--") else {
+            return self.parse_short_options(&arg[1..], args);
+        };
+
+        let (name, inline) = match long.split_once('=') {
+            Some((name, value)) => (name, Some(value.to_string())),
+            None => (long, None),
+        };
+
+        match name {
+            // `--color` on its own means `--color=auto`, like in `grep`.
+            "color" | "colour" => {
+                self.color = match &inline {
+                    Some(choice) => choice.parse()?,
+                    None => ColorChoice::Auto,
+                }
+            }
+            _ if takes_value(name) => {
+                let value = inline
+                    .or_else(|| args.next())
+                    .ok_or_else(|| format!("Option '--{name}' requires a value"))?;
+                self.set_value(name, &value)?;
+            }
+            _ if !self.set_long_flag(name) => return Err(format!("Unknown option '--{name}'")),
+            _ if inline.is_some() => {
+                return Err(format!("Option '--{name}' doesn't take a value"));
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// Applies a cluster of short options such as `-in` or `-nC2`.
+    ///
+    /// An option that takes a value uses the rest of the cluster as its
+    /// value, or the next argument if the cluster ends with it.
+    fn parse_short_options(
+        &mut self,
+        flags: &str,
+        args: &mut dyn Iterator<Item = String>,
+    ) -> Result<(), String> {
+        for (index, flag) in flags.char_indices() {
+            let Some(option) = short_option(flag) else {
+                return Err(format!("Unknown option '-{flag}'"));
+            };
+
+            if !matches!(option.value, Value::Required(_)) {
+                self.set_long_flag(option.long);
+                continue;
+            }
+
+            let rest = &flags[index + flag.len_utf8()..];
+            let value = if rest.is_empty() {
+                args.next()
+                    .ok_or_else(|| format!("Option '-{flag}' requires a value"))?
+            } else {
+                rest.to_string()
+            };
+
+            return self.set_value(option.long, &value);
+        }
+
+        Ok(())
+    }
+
+    /// Sets the field behind a long option that takes a value.
+    fn set_value(&mut self, name: &str, value: &str) -> Result<(), String> {
+        match name {
+            "format" => self.format = Some(value.parse()?),
+            "max-columns" => self.max_columns = Some(parse_number(name, value)?),
+            "after-context" => self.after_context = parse_number(name, value)?,
+            "before-context" => self.before_context = parse_number(name, value)?,
+            "max-matches-per-line" => self.max_matches_per_line = Some(parse_number(name, value)?),
+            "max-distance" => self.fuzzy = Some(parse_number(name, value)?),
+            "max-time" => {
+                let secs = parse_number(name, value)?;
+                let budget = Duration::try_from_secs_f64(secs).map_err(|_| {
+                    format!("Invalid value '{value}' for '--{name}': expected a number of seconds")
+                })?;
+                self.max_time = Some(budget);
+            }
+            "regex-timeout" => {
+                self.regex_timeout = Some(Duration::from_millis(parse_number(name, value)?));
+            }
+            "normalize" => self.normalize = Some(value.parse()?),
+            "output-encoding" => self.output_encoding = value.parse()?,
+            "split-output" => self.split_output = Some(value.to_string()),
+            "split-by-pattern" => self.split_by_pattern = Some(value.to_string()),
+            "output" => self.output = Some(value.to_string()),
+            "hex" => self.hex = Some(parse_hex(value)?),
+            "from" => self.from = Some(value.to_string()),
+            "to" => self.to = Some(value.to_string()),
+            "regexp" => self.patterns.push(value.to_string()),
+            "exclude-dir" => self.exclude_dir.push(value.to_string()),
+            "type" => self.file_types.push(value.to_string()),
+            "type-add" => self.type_add.push(value.to_string()),
+            "pre" => self.pre = Some(value.to_string()),
+            "only-in" => self.only_in = Some(value.to_string()),
+            "also-matching" => self.also_matching = Some(value.to_string()),
+            "exclude-matching" => self.exclude_matching = Some(value.to_string()),
+            "buffer-size" => self.buffer_size = Some(parse_size(name, value, MAX_BUFFER_SIZE)?),
+            "sample" => self.sample = Some(parse_number(name, value)?),
+            "max-total" => self.max_total = Some(parse_number(name, value)?),
+            "max-files" => self.max_files = Some(parse_number(name, value)?),
+            "skip" => self.skip = parse_number(name, value)?,
+            "min-match-len" => self.min_match_len = parse_number(name, value)?,
+            "pager" => self.pager = Some(value.to_string()),
+            "seek-bytes" => self.seek_bytes = parse_number(name, value)?,
+            "threads" => self.threads = Some(parse_nonzero(name, value)?),
+            "bucket-size" => self.bucket_size = Some(parse_nonzero(name, value)?),
+            "percentile" => self.percentile = Some(parse_percentile(name, value)?),
+            "seed" => self.seed = Some(parse_number(name, value)?),
+            "context" => {
+                self.after_context = parse_number(name, value)?;
+                self.before_context = self.after_context;
+            }
+            _ => unreachable!("'--{name}' doesn't take a value"),
+        }
+
+        Ok(())
+    }
+
+    /// Sets the boolean field behind a long flag, returning `false` if there
+    /// is no such flag.
+    fn set_long_flag(&mut self, name: &str) -> bool {
+        match name {
+            "help" => self.help = true,
+            "list-options" => self.list_options = true,
+            "version" => self.version = true,
+            "ignore-case" => self.ignore_case = true,
+            "regex" => self.regex = true,
+            "multiline" => {
+                self.regex = true;
+                self.multiline = true;
+            }
+            "multiline-dotall" => {
+                self.regex = true;
+                self.multiline = true;
+                self.multiline_dotall = true;
+            }
+            "flatten-matches" => self.flatten_matches = true,
+            "whole-file-match" => {
+                self.regex = true;
+                self.whole_file_match = true;
+            }
+            "slurp" => self.slurp = true,
+            "fuzzy" => self.fuzzy = Some(self.fuzzy.unwrap_or(1)),
+            "squeeze-whitespace" => self.squeeze_whitespace = true,
+            "word-regexp" => self.word_regexp = true,
+            "word-start" => self.word_start = true,
+            "find-non-ascii" => self.find_non_ascii = true,
+            "strict-utf8" => self.strict_utf8 = true,
+            "pattern-clipboard" => self.pattern_clipboard = true,
+            "strip-bom" => self.strip_bom = true,
+            "json-input" => self.json_input = true,
+            "match-invalid-json" => self.match_invalid_json = true,
+            "invert-match" => self.invert_match = true,
+            "line-number" => self.line_number = true,
+            "only-matching" => self.only_matching = true,
+            "merge-adjacent" => self.merge_adjacent = true,
+            "byte-offset" => self.byte_offset = true,
+            "offset-relative" => self.offset_relative = true,
+            "ascii-case" => {
+                self.ignore_case = true;
+                self.ascii_case = true;
+            }
+            "case-variants" => self.case_variants = true,
+            "recursive" => self.recursive = true,
+            "follow" => self.follow = true,
+            "stdin-paths" => self.stdin_paths = true,
+            "symmetric" => self.symmetric = true,
+            "null" => self.null = true,
+            "decompress-stdin" => self.decompress_stdin = true,
+            "quiet-errors" => self.quiet_errors = true,
+            "fail-fast" => self.fail_fast = true,
+            "skip-empty" => self.skip_empty = true,
+            "append" => self.append = true,
+            "compress" => self.compress = true,
+            "no-pager" => self.no_pager = true,
+            "respect-crlf" => self.respect_crlf = true,
+            "no-trailing-newline" => self.no_trailing_newline = true,
+            "bottom-up" => self.bottom_up = true,
+            "glob-ignore-case" => self.glob_ignore_case = true,
+            "dedupe-files" => self.dedupe_files = true,
+            "skip-duplicate-content" => self.skip_duplicate_content = true,
+            "report-empty" => self.report_empty = true,
+            "reverse-file-order" => self.reverse_file_order = true,
+            "line-buffered" => self.line_buffered = true,
+            "timestamp" => self.timestamp = true,
+            "csv" => self.csv = true,
+            "csv-header" => {
+                self.csv = true;
+                self.csv_header = true;
+            }
+            "json" | "json-lines" => self.json = true,
+            "json-array" => {
+                self.json = true;
+                self.json_array = true;
+            }
+            "mark" => self.mark = true,
+            "dim-context" => self.dim_context = true,
+            "escape-nonprinting" => self.escape_nonprinting = true,
+            "passthru" => self.passthru = true,
+            "print-whole-file" => self.print_whole_file = true,
+            "file-all" => self.file_all = true,
+            "ratio" => self.ratio = true,
+            "density" => self.density = true,
+            "summary" => self.summary = true,
+            "summary-json" => self.summary_json = true,
+            "group-count" => self.group_count = true,
+            "length-histogram" => self.length_histogram = true,
+            "sort-lines" => self.sort_lines = true,
+            "reverse" => self.reverse = true,
+            "count" => self.count = true,
+            "count-total" => self.count_total = true,
+            "count-by-pattern" => self.count_by_pattern = true,
+            "pattern-file-coverage" => self.pattern_file_coverage = true,
+            "cooccurrence" => self.cooccurrence = true,
+            "report-unmatched" => self.report_unmatched = true,
+            "label-patterns" => self.label_patterns = true,
+            "char-count" => self.char_count = true,
+            "columns" => {
+                self.line_number = true;
+                self.columns = true;
+            }
+            "numbers-only" => self.numbers_only = true,
+            "gaps" => self.gaps = true,
+            "parallel-within-file" => self.parallel_within_file = true,
+            "continuous-lineno" => self.continuous_lineno = true,
+            "include-partial" => self.include_partial = true,
+            "longest" => self.longest = true,
+            "first-only" => self.first_only = true,
+            "measure-throughput" => self.measure_throughput = true,
+            "files-with-counts" => self.files_with_counts = true,
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/// A command-line option, as listed by `--help`.
+struct OptionSpec {
+    short: Option<char>,
+    long: &'static str,
+    value: Value,
+    help: &'static str,
+}
+
+/// Whether an option takes a value, and its name in the `--help` output.
+enum Value {
+    None,
+    /// Given as `--name=VALUE`, `--name VALUE` or, for short options,
+    /// `-xVALUE` and `-x VALUE`.
+    Required(&'static str),
+    /// Only given as `--name=VALUE`.
+    Optional(&'static str),
+}
+
+/// All options, in the order they are listed by `--help`. Parsing looks up
+/// short options and whether an option takes a value here.
+const OPTIONS: &[OptionSpec] = &[
+    OptionSpec {
+        short: None,
+        long: "help",
+        value: Value::None,
+        help: "Print this help and exit",
+    },
+    OptionSpec {
+        short: None,
+        long: "list-options",
+        value: Value::None,
+        help: "Print all options in a machine-readable form and exit",
+    },
+    OptionSpec {
+        short: None,
+        long: "version",
+        value: Value::None,
+        help: "Print the version and the compiled-in features and exit",
+    },
+    OptionSpec {
+        short: Some('e'),
+        long: "regexp",
+        value: Value::Required("PATTERN"),
+        help: "Search for PATTERN instead of the query; may be repeated",
+    },
+    OptionSpec {
+        short: Some('i'),
+        long: "ignore-case",
+        value: Value::None,
+        help: "Case-insensitive search",
+    },
+    OptionSpec {
+        short: None,
+        long: "ascii-case",
+        value: Value::None,
+        help: "Case-insensitive search folding ASCII letters only",
+    },
+    OptionSpec {
+        short: None,
+        long: "case-variants",
+        value: Value::None,
+        help: "Match the query as is, lowercase, uppercase or title case",
+    },
+    OptionSpec {
+        short: Some('E'),
+        long: "regex",
+        value: Value::None,
+        help: "Interpret the query as a regular expression",
+    },
+    OptionSpec {
+        short: None,
+        long: "regex-timeout",
+        value: Value::Required("MS"),
+        help: "Skip lines that take longer than MS milliseconds to match",
+    },
+    OptionSpec {
+        short: Some('U'),
+        long: "multiline",
+        value: Value::None,
+        help: "Let regular expression matches span several lines",
+    },
+    OptionSpec {
+        short: None,
+        long: "multiline-dotall",
+        value: Value::None,
+        help: "Like --multiline, with . matching line breaks too",
+    },
+    OptionSpec {
+        short: None,
+        long: "flatten-matches",
+        value: Value::None,
+        help: "With --multiline, print the line breaks in matches as \\n",
+    },
+    OptionSpec {
+        short: None,
+        long: "whole-file-match",
+        value: Value::None,
+        help: "Only check if the regular expression matches each whole file",
+    },
+    OptionSpec {
+        short: None,
+        long: "slurp",
+        value: Value::None,
+        help: "Search each whole file as one string, printing snippets",
+    },
+    OptionSpec {
+        short: None,
+        long: "hex",
+        value: Value::Required("BYTES"),
+        help: "Search the raw bytes of the files for hex BYTES instead of a query",
+    },
+    OptionSpec {
+        short: None,
+        long: "find-non-ascii",
+        value: Value::None,
+        help: "Report the lines with non-ASCII bytes instead of searching",
+    },
+    OptionSpec {
+        short: None,
+        long: "strict-utf8",
+        value: Value::None,
+        help: "Fail on the first file that isn't valid UTF-8, with the offset",
+    },
+    OptionSpec {
+        short: None,
+        long: "pattern-clipboard",
+        value: Value::None,
+        help: "Take the query from the clipboard instead of the arguments",
+    },
+    OptionSpec {
+        short: None,
+        long: "normalize",
+        value: Value::Required("FORM"),
+        help: "Normalize the query and the input to NFC or NFD before matching",
+    },
+    OptionSpec {
+        short: None,
+        long: "fuzzy",
+        value: Value::None,
+        help: "Match substrings within one edit of the query",
+    },
+    OptionSpec {
+        short: None,
+        long: "max-distance",
+        value: Value::Required("N"),
+        help: "Like --fuzzy, allowing up to N edits",
+    },
+    OptionSpec {
+        short: None,
+        long: "squeeze-whitespace",
+        value: Value::None,
+        help: "Treat runs of whitespace in the query and the lines as one space",
+    },
+    OptionSpec {
+        short: Some('w'),
+        long: "word-regexp",
+        value: Value::None,
+        help: "Only match whole words",
+    },
+    OptionSpec {
+        short: None,
+        long: "word-start",
+        value: Value::None,
+        help: "Only match at the start of words",
+    },
+    OptionSpec {
+        short: None,
+        long: "min-match-len",
+        value: Value::Required("N"),
+        help: "Only count matches at least N characters long",
+    },
+    OptionSpec {
+        short: None,
+        long: "from",
+        value: Value::Required("PATTERN"),
+        help: "Only search from each line containing PATTERN",
+    },
+    OptionSpec {
+        short: None,
+        long: "to",
+        value: Value::Required("PATTERN"),
+        help: "Stop searching at each line containing PATTERN",
+    },
+    OptionSpec {
+        short: None,
+        long: "json-input",
+        value: Value::None,
+        help: "Match only the string values of lines of JSON objects",
+    },
+    OptionSpec {
+        short: None,
+        long: "match-invalid-json",
+        value: Value::None,
+        help: "With --json-input, match lines that aren't JSON as text",
+    },
+    OptionSpec {
+        short: None,
+        long: "also-matching",
+        value: Value::Required("PATTERN"),
+        help: "Only keep the selected lines that also match PATTERN",
+    },
+    OptionSpec {
+        short: None,
+        long: "exclude-matching",
+        value: Value::Required("PATTERN"),
+        help: "Drop the selected lines that also match PATTERN",
+    },
+    OptionSpec {
+        short: None,
+        long: "strip-bom",
+        value: Value::None,
+        help: "Remove a UTF-8 byte order mark from the start of each file",
+    },
+    OptionSpec {
+        short: Some('v'),
+        long: "invert-match",
+        value: Value::None,
+        help: "Select the lines that don't contain the query",
+    },
+    OptionSpec {
+        short: Some('n'),
+        long: "line-number",
+        value: Value::None,
+        help: "Prefix each line with its line number",
+    },
+    OptionSpec {
+        short: None,
+        long: "continuous-lineno",
+        value: Value::None,
+        help: "Number the lines of all files on as if they were one file",
+    },
+    OptionSpec {
+        short: Some('A'),
+        long: "after-context",
+        value: Value::Required("N"),
+        help: "Print N lines after each selected line",
+    },
+    OptionSpec {
+        short: Some('B'),
+        long: "before-context",
+        value: Value::Required("N"),
+        help: "Print N lines before each selected line",
+    },
+    OptionSpec {
+        short: Some('C'),
+        long: "context",
+        value: Value::Required("N"),
+        help: "Print N lines before and after each selected line",
+    },
+    OptionSpec {
+        short: Some('o'),
+        long: "only-matching",
+        value: Value::None,
+        help: "Print only the matching parts of each line",
+    },
+    OptionSpec {
+        short: None,
+        long: "merge-adjacent",
+        value: Value::None,
+        help: "Report touching matches as a single match",
+    },
+    OptionSpec {
+        short: None,
+        long: "max-matches-per-line",
+        value: Value::Required("N"),
+        help: "With -o, print at most N matches of each line",
+    },
+    OptionSpec {
+        short: Some('b'),
+        long: "byte-offset",
+        value: Value::None,
+        help: "Prefix each line or match with its byte offset",
+    },
+    OptionSpec {
+        short: None,
+        long: "offset-relative",
+        value: Value::None,
+        help: "With -o -b, count offsets from the start of the line",
+    },
+    OptionSpec {
+        short: Some('c'),
+        long: "count",
+        value: Value::None,
+        help: "Print the number of selected lines of each file",
+    },
+    OptionSpec {
+        short: None,
+        long: "count-total",
+        value: Value::None,
+        help: "Print the number of selected lines of all files last",
+    },
+    OptionSpec {
+        short: None,
+        long: "max-total",
+        value: Value::Required("N"),
+        help: "Stop searching after N selected lines across all files",
+    },
+    OptionSpec {
+        short: None,
+        long: "max-time",
+        value: Value::Required("SECS"),
+        help: "Stop searching after SECS seconds, with a note on stderr",
+    },
+    OptionSpec {
+        short: None,
+        long: "skip",
+        value: Value::Required("N"),
+        help: "Drop the first N selected lines of each file",
+    },
+    OptionSpec {
+        short: None,
+        long: "bottom-up",
+        value: Value::None,
+        help: "Search the lines of each file from the last one up",
+    },
+    OptionSpec {
+        short: None,
+        long: "measure-throughput",
+        value: Value::None,
+        help: "Print the bytes searched per second to standard error",
+    },
+    OptionSpec {
+        short: None,
+        long: "count-by-pattern",
+        value: Value::None,
+        help: "Print the number of selected lines of each file per pattern",
+    },
+    OptionSpec {
+        short: None,
+        long: "pattern-file-coverage",
+        value: Value::None,
+        help: "Print the number of files matching each pattern",
+    },
+    OptionSpec {
+        short: None,
+        long: "cooccurrence",
+        value: Value::None,
+        help: "Print the number of lines matching each pair of patterns",
+    },
+    OptionSpec {
+        short: None,
+        long: "report-unmatched",
+        value: Value::None,
+        help: "Report the patterns that matched no line on standard error",
+    },
+    OptionSpec {
+        short: None,
+        long: "label-patterns",
+        value: Value::None,
+        help: "Prefix each line with the patterns it matches, named with NAME=PATTERN",
+    },
+    OptionSpec {
+        short: None,
+        long: "char-count",
+        value: Value::None,
+        help: "Prefix each line with its length in characters",
+    },
+    OptionSpec {
+        short: None,
+        long: "columns",
+        value: Value::None,
+        help: "Print the line number and start-end columns of each match",
+    },
+    OptionSpec {
+        short: None,
+        long: "numbers-only",
+        value: Value::None,
+        help: "Print only the numbers of the selected lines of each file",
+    },
+    OptionSpec {
+        short: None,
+        long: "gaps",
+        value: Value::None,
+        help: "Print the line gaps between the selected lines of each file",
+    },
+    OptionSpec {
+        short: None,
+        long: "first-only",
+        value: Value::None,
+        help: "Print only the position of the first match of each file",
+    },
+    OptionSpec {
+        short: None,
+        long: "files-with-counts",
+        value: Value::None,
+        help: "Print each file with selected lines once, with their count",
+    },
+    OptionSpec {
+        short: Some('r'),
+        long: "recursive",
+        value: Value::None,
+        help: "Search directories recursively",
+    },
+    OptionSpec {
+        short: None,
+        long: "follow",
+        value: Value::None,
+        help: "Follow symbolic links while searching recursively",
+    },
+    OptionSpec {
+        short: None,
+        long: "pre",
+        value: Value::Required("COMMAND"),
+        help: "Search the output of COMMAND run with each file instead",
+    },
+    OptionSpec {
+        short: None,
+        long: "exclude-dir",
+        value: Value::Required("GLOB"),
+        help: "Skip directories named like GLOB while searching recursively",
+    },
+    OptionSpec {
+        short: None,
+        long: "type",
+        value: Value::Required("NAME"),
+        help: "Only search files of type NAME, such as rust, while searching recursively",
+    },
+    OptionSpec {
+        short: None,
+        long: "type-add",
+        value: Value::Required("TYPE"),
+        help: "Add a file type, or globs to one, as NAME:GLOB",
+    },
+    OptionSpec {
+        short: None,
+        long: "glob-ignore-case",
+        value: Value::None,
+        help: "Match the globs of --exclude-dir and --type regardless of case",
+    },
+    OptionSpec {
+        short: None,
+        long: "max-files",
+        value: Value::Required("N"),
+        help: "Search only the first N files",
+    },
+    OptionSpec {
+        short: None,
+        long: "reverse-file-order",
+        value: Value::None,
+        help: "Search the files in the reverse order",
+    },
+    OptionSpec {
+        short: None,
+        long: "seek-bytes",
+        value: Value::Required("N"),
+        help: "Start searching each file at byte N",
+    },
+    OptionSpec {
+        short: None,
+        long: "include-partial",
+        value: Value::None,
+        help: "Search the line --seek-bytes falls into too",
+    },
+    OptionSpec {
+        short: None,
+        long: "stdin-paths",
+        value: Value::None,
+        help: "Read the paths to search from standard input, one per line",
+    },
+    OptionSpec {
+        short: Some('Z'),
+        long: "null",
+        value: Value::None,
+        help: "With --stdin-paths, the paths are separated by NUL bytes",
+    },
+    OptionSpec {
+        short: Some('z'),
+        long: "decompress-stdin",
+        value: Value::None,
+        help: "Decompress standard input if it is compressed",
+    },
+    OptionSpec {
+        short: None,
+        long: "only-in",
+        value: Value::Required("FILE"),
+        help: "Print the selected lines of FILE that the files don't have",
+    },
+    OptionSpec {
+        short: None,
+        long: "symmetric",
+        value: Value::None,
+        help: "With --only-in, also print those of the files that FILE doesn't have",
+    },
+    OptionSpec {
+        short: None,
+        long: "dedupe-files",
+        value: Value::None,
+        help: "Search each file only once",
+    },
+    OptionSpec {
+        short: None,
+        long: "skip-duplicate-content",
+        value: Value::None,
+        help: "Skip files with the same contents as one searched before",
+    },
+    OptionSpec {
+        short: None,
+        long: "report-empty",
+        value: Value::None,
+        help: "Note the empty files on stderr",
+    },
+    OptionSpec {
+        short: None,
+        long: "quiet-errors",
+        value: Value::None,
+        help: "Skip unreadable files silently, exiting with status 2",
+    },
+    OptionSpec {
+        short: None,
+        long: "fail-fast",
+        value: Value::None,
+        help: "Stop the search at the first file that can't be read",
+    },
+    OptionSpec {
+        short: None,
+        long: "format",
+        value: Value::Required("TEMPLATE"),
+        help: "Print matches with a {path} {lineno} {text} {count} template",
+    },
+    OptionSpec {
+        short: None,
+        long: "max-columns",
+        value: Value::Required("N"),
+        help: "Truncate printed lines to N characters",
+    },
+    OptionSpec {
+        short: None,
+        long: "csv",
+        value: Value::None,
+        help: "Print matches as path,line_number,text CSV rows",
+    },
+    OptionSpec {
+        short: None,
+        long: "csv-header",
+        value: Value::None,
+        help: "Like --csv, with a header row first",
+    },
+    OptionSpec {
+        short: None,
+        long: "json",
+        value: Value::None,
+        help: "Print matches as JSON objects, one per line (or --json-lines)",
+    },
+    OptionSpec {
+        short: None,
+        long: "json-array",
+        value: Value::None,
+        help: "Print matches as a single JSON array",
+    },
+    OptionSpec {
+        short: None,
+        long: "output-encoding",
+        value: Value::Required("ENCODING"),
+        help: "Encode the output as utf-8 (default), utf-16le or utf-16be",
+    },
+    OptionSpec {
+        short: None,
+        long: "respect-crlf",
+        value: Value::None,
+        help: "End printed lines with \\r\\n if they did in their file",
+    },
+    OptionSpec {
+        short: None,
+        long: "no-trailing-newline",
+        value: Value::None,
+        help: "Leave out the line break after the last line of output",
+    },
+    OptionSpec {
+        short: None,
+        long: "split-output",
+        value: Value::Required("DIR"),
+        help: "Write the output of each file to DIR/<path>.matches",
+    },
+    OptionSpec {
+        short: None,
+        long: "split-by-pattern",
+        value: Value::Required("DIR"),
+        help: "Write the lines matching the Nth pattern to DIR/N.txt",
+    },
+    OptionSpec {
+        short: None,
+        long: "skip-empty",
+        value: Value::None,
+        help: "With --split-output, skip files without output",
+    },
+    OptionSpec {
+        short: None,
+        long: "output",
+        value: Value::Required("FILE"),
+        help: "Write the output to FILE instead of standard output",
+    },
+    OptionSpec {
+        short: None,
+        long: "append",
+        value: Value::None,
+        help: "Append to the output files instead of replacing them",
+    },
+    OptionSpec {
+        short: None,
+        long: "compress",
+        value: Value::None,
+        help: "Compress the --output file with gzip",
+    },
+    OptionSpec {
+        short: None,
+        long: "pager",
+        value: Value::Required("CMD"),
+        help: "Show the output in CMD when stdout is a terminal (less -R)",
+    },
+    OptionSpec {
+        short: None,
+        long: "no-pager",
+        value: Value::None,
+        help: "Never show the output in a pager",
+    },
+    OptionSpec {
+        short: None,
+        long: "color",
+        value: Value::Optional("WHEN"),
+        help: "Highlight matches: never, always or auto",
+    },
+    OptionSpec {
+        short: None,
+        long: "mark",
+        value: Value::None,
+        help: "Highlight matches with >> and <<",
+    },
+    OptionSpec {
+        short: None,
+        long: "dim-context",
+        value: Value::None,
+        help: "With colors, dim the text around the matches instead",
+    },
+    OptionSpec {
+        short: None,
+        long: "escape-nonprinting",
+        value: Value::None,
+        help: "Show control characters as escapes like ^[ and ^M",
+    },
+    OptionSpec {
+        short: None,
+        long: "passthru",
+        value: Value::None,
+        help: "Print every line, highlighting the matches",
+    },
+    OptionSpec {
+        short: None,
+        long: "print-whole-file",
+        value: Value::None,
+        help: "Print the whole of each file that matches",
+    },
+    OptionSpec {
+        short: None,
+        long: "file-all",
+        value: Value::None,
+        help: "Only search the files where every pattern matches some line",
+    },
+    OptionSpec {
+        short: None,
+        long: "buffer-size",
+        value: Value::Required("BYTES"),
+        help: "Read input files with a buffer of this size, such as 64K",
+    },
+    OptionSpec {
+        short: Some('j'),
+        long: "threads",
+        value: Value::Required("N"),
+        help: "Search N files at a time on separate threads",
+    },
+    OptionSpec {
+        short: None,
+        long: "parallel-within-file",
+        value: Value::None,
+        help: "Split each file into parts searched on the threads instead",
+    },
+    OptionSpec {
+        short: None,
+        long: "line-buffered",
+        value: Value::None,
+        help: "Flush each match as soon as it is found",
+    },
+    OptionSpec {
+        short: None,
+        long: "timestamp",
+        value: Value::None,
+        help: "Prefix each match with the time it was found",
+    },
+    OptionSpec {
+        short: None,
+        long: "sample",
+        value: Value::Required("N"),
+        help: "Print only N matches chosen at random",
+    },
+    OptionSpec {
+        short: None,
+        long: "seed",
+        value: Value::Required("N"),
+        help: "Seed for --sample, to get the same sample every time",
+    },
+    OptionSpec {
+        short: None,
+        long: "ratio",
+        value: Value::None,
+        help: "Print the share of selected lines of each file",
+    },
+    OptionSpec {
+        short: None,
+        long: "density",
+        value: Value::None,
+        help: "Print how clustered the selected lines of each file are",
+    },
+    OptionSpec {
+        short: None,
+        long: "summary",
+        value: Value::None,
+        help: "Print the lines and bytes, total and selected, of each file",
+    },
+    OptionSpec {
+        short: None,
+        long: "summary-json",
+        value: Value::None,
+        help: "Print a JSON summary of the search to stderr",
+    },
+    OptionSpec {
+        short: None,
+        long: "group-count",
+        value: Value::None,
+        help: "Print each distinct matching line once with its count",
+    },
+    OptionSpec {
+        short: None,
+        long: "length-histogram",
+        value: Value::None,
+        help: "Print a histogram of the lengths of the selected lines",
+    },
+    OptionSpec {
+        short: None,
+        long: "bucket-size",
+        value: Value::Required("N"),
+        help: "Width of the buckets of --length-histogram (default 10)",
+    },
+    OptionSpec {
+        short: None,
+        long: "percentile",
+        value: Value::Required("P"),
+        help: "Print the P-th percentile of the lengths of the selected lines",
+    },
+    OptionSpec {
+        short: None,
+        long: "sort-lines",
+        value: Value::None,
+        help: "Print the selected lines of each file sorted",
+    },
+    OptionSpec {
+        short: None,
+        long: "reverse",
+        value: Value::None,
+        help: "Sort in descending order with --sort-lines",
+    },
+    OptionSpec {
+        short: None,
+        long: "longest",
+        value: Value::None,
+        help: "Print only the longest selected line of each file",
+    },
+];
+
+/// Returns the usage summary printed by `--help`, listing every option.
+///
+/// `-h` is deliberately not an alias of `--help`: in `grep` it suppresses
+/// file names.
+///
+/// # Examples
+///
+/// ```
+/// let help = simple_grep::help();
+///
+/// assert!(help.contains("-i, --ignore-case"));
+/// assert!(help.contains("--color[=WHEN]"));
+/// ```
+pub fn help() -> String {
+    let names: Vec<String> = OPTIONS
+        .iter()
+        .map(|option| {
+            let short = option
+                .short
+                .map_or(String::from("    "), |c| format!("-{c}, "));
+            let value = match option.value {
+                Value::None => String::new(),
+                Value::Required(name) => format!(" {name}"),
+                Value::Optional(name) => format!("[={name}]"),
+            };
+            format!("{short}--{}{value}", option.long)
+        })
+        .collect();
+    let width = names.iter().map(String::len).max().unwrap_or(0);
+
+    let mut help =
+        String::from("Usage: simple_grep [OPTIONS] <query> <file_path>...\n\nOptions:\n");
+
+    for (name, option) in names.iter().zip(OPTIONS) {
+        help.push_str(&format!("  {name:width$}  {}\n", option.help));
+    }
+
+    help
+}
+
+/// Returns every option in a stable, machine-readable form for `--list-options`,
+/// such as for generating shell completions: one line per option, in the
+/// order of [`help`], with four tab-separated fields: the long name, the
+/// short name or nothing, whether it takes a value (`no`, `yes` or
+/// `optional`), and its description.
+///
+/// # Examples
+///
+/// ```
+/// let options = simple_grep::list_options();
+///
+/// assert!(options.contains("--ignore-case\t-i\tno\tCase-insensitive search\n"));
+/// assert!(options.contains("--after-context\t-A\tyes\t"));
+/// ```
+pub fn list_options() -> String {
+    let mut list = String::new();
+
+    for option in OPTIONS {
+        let short = option.short.map(|c| format!("-{c}")).unwrap_or_default();
+        let value = match option.value {
+            Value::None => "no",
+            Value::Required(_) => "yes",
+            Value::Optional(_) => "optional",
+        };
+        list.push_str(&format!(
+            "--{}\t{short}\t{value}\t{}\n",
+            option.long, option.help
+        ));
+    }
+
+    list
+}
+
+/// Returns the option with the short name `flag`.
+fn short_option(flag: char) -> Option<&'static OptionSpec> {
+    OPTIONS.iter().find(|option| option.short == Some(flag))
+}
+
+/// Returns `true` if the long option `--name` requires a value.
+fn takes_value(name: &str) -> bool {
+    OPTIONS
+        .iter()
+        .any(|option| option.long == name && matches!(option.value, Value::Required(_)))
+}
+
+/// Parses the numeric value of option `--name`.
+fn parse_number<T: FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value '{value}' for '--{name}': expected a number"))
+}
+
+/// Parses the numeric value of option `--name`, which must not be 0.
+fn parse_nonzero(name: &str, value: &str) -> Result<usize, String> {
+    match parse_number(name, value)? {
+        0 => Err(format!(
+            "Invalid value '{value}' for '--{name}': must not be 0"
+        )),
+        number => Ok(number),
+    }
+}
+
+/// Parses the percentile of option `--name`, above 0 and up to 100.
+fn parse_percentile(name: &str, value: &str) -> Result<f64, String> {
+    match parse_number(name, value)? {
+        percentile if percentile > 0.0 && percentile <= 100.0 => Ok(percentile),
+        _ => Err(format!(
+            "Invalid value '{value}' for '--{name}': expected a percentile above 0 and up to 100"
+        )),
+    }
+}
+
+/// Parses the size in bytes of option `--name`, such as `4096`, `64K`
+/// or `1M`, up to `max`.
+fn parse_size(name: &str, value: &str, max: usize) -> Result<usize, String> {
+    let (digits, unit) = match value.char_indices().last() {
+        Some((index, 'k' | 'K')) => (&value[..index], 1 << 10),
+        Some((index, 'm' | 'M')) => (&value[..index], 1 << 20),
+        Some((index, 'g' | 'G')) => (&value[..index], 1 << 30),
+        _ => (value, 1),
+    };
+
+    let invalid = || format!("Invalid value '{value}' for '--{name}': expected a size such as 64K");
+
+    match digits
+        .parse::<usize>()
+        .ok()
+        .and_then(|n| n.checked_mul(unit))
+    {
+        Some(0) => Err(format!(
+            "Invalid value '{value}' for '--{name}': size must not be 0"
+        )),
+        Some(size) if size > max => Err(format!(
+            "Invalid value '{value}' for '--{name}': size must be at most {}G",
+            max >> 30
+        )),
+        Some(size) => Ok(size),
+        None => Err(invalid()),
+    }
+}
+
+/// Returns `true` if `arg` looks like an option rather than a positional argument.
+fn is_option(arg: &str) -> bool {
+    arg.len() > 1 && arg.starts_with('-')
+}
@@ -0,0 +1,114 @@
+//! Encoding of the output for `--output-encoding`.
+
+use std::io::{self, Write};
+use std::str::FromStr;
+
+/// The character encoding of the output, set with `--output-encoding`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum OutputEncoding {
+    /// UTF-8, the encoding used internally, written as it is.
+    #[default]
+    Utf8,
+    /// UTF-16, little-endian, starting with a byte order mark.
+    Utf16Le,
+    /// UTF-16, big-endian, starting with a byte order mark.
+    Utf16Be,
+}
+
+impl FromStr for OutputEncoding {
+    type Err = String;
+
+    fn from_str(encoding: &str) -> Result<OutputEncoding, String> {
+        match encoding.to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Ok(OutputEncoding::Utf8),
+            "utf-16le" | "utf16le" => Ok(OutputEncoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(OutputEncoding::Utf16Be),
+            _ => Err(format!(
+                "Invalid output encoding '{encoding}': expected 'utf-8', 'utf-16le' or 'utf-16be'"
+            )),
+        }
+    }
+}
+
+/// Re-encodes the UTF-8 written to it as UTF-16 before passing it on.
+///
+/// The byte order mark is written before the first character. A character
+/// split across several writes is held back until it is complete, and
+/// invalid UTF-8 is replaced with U+FFFD.
+pub(crate) struct Utf16Writer<W: Write> {
+    inner: W,
+    little_endian: bool,
+    /// Whether the byte order mark has been written.
+    started: bool,
+    /// The start of a character whose remaining bytes haven't been written yet.
+    partial: Vec<u8>,
+}
+
+impl<W: Write> Utf16Writer<W> {
+    pub(crate) fn new(inner: W, little_endian: bool) -> Utf16Writer<W> {
+        Utf16Writer {
+            inner,
+            little_endian,
+            started: false,
+            partial: Vec::new(),
+        }
+    }
+
+    fn encode(&self, text: &str, encoded: &mut Vec<u8>) {
+        for unit in text.encode_utf16() {
+            let bytes = if self.little_endian {
+                unit.to_le_bytes()
+            } else {
+                unit.to_be_bytes()
+            };
+            encoded.extend_from_slice(&bytes);
+        }
+    }
+}
+
+impl<W: Write> Write for Utf16Writer<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut encoded = Vec::new();
+
+        if !self.started {
+            self.encode("\u{feff}", &mut encoded);
+            self.started = true;
+        }
+
+        let mut bytes = std::mem::take(&mut self.partial);
+        bytes.extend_from_slice(buf);
+        let mut rest = &bytes[..];
+
+        loop {
+            match std::str::from_utf8(rest) {
+                Ok(text) => {
+                    self.encode(text, &mut encoded);
+                    break;
+                }
+                Err(err) => {
+                    let (valid, invalid) = rest.split_at(err.valid_up_to());
+                    // The prefix was just validated.
+                    self.encode(std::str::from_utf8(valid).unwrap_or_default(), &mut encoded);
+
+                    match err.error_len() {
+                        Some(len) => {
+                            self.encode("\u{fffd}", &mut encoded);
+                            rest = &invalid[len..];
+                        }
+                        None => {
+                            self.partial = invalid.to_vec();
+                            break;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.inner.write_all(&encoded)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,121 @@
+//! Errors reported while searching.
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::output::json_string;
+
+/// A file that couldn't be searched, such as one that doesn't exist.
+///
+/// # Examples
+///
+/// ```
+/// use std::io;
+/// use std::path::PathBuf;
+///
+/// use simple_grep::FileError;
+///
+/// let err = FileError {
+///     path: PathBuf::from("a.txt"),
+///     source: io::Error::new(io::ErrorKind::NotFound, "No such file").into(),
+/// };
+///
+/// assert_eq!(err.to_string(), "a.txt: No such file");
+/// assert_eq!(
+///     err.to_json(),
+///     r#"{"error":"failed to read","path":"a.txt","cause":"No such file"}"#
+/// );
+/// ```
+#[derive(Debug)]
+pub struct FileError {
+    /// The file, as given or found while searching recursively.
+    pub path: PathBuf,
+    /// Why it couldn't be searched.
+    pub source: Box<dyn Error>,
+}
+
+impl FileError {
+    /// Describes the error as a JSON object with `error`, `path` and `cause`
+    /// fields, as printed in `--json` modes.
+    pub fn to_json(&self) -> String {
+        format!(
+            r#"{{"error":"failed to read","path":{},"cause":{}}}"#,
+            json_string(&self.path.display().to_string()),
+            json_string(&self.source.to_string())
+        )
+    }
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.source)
+    }
+}
+
+impl Error for FileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.source.as_ref())
+    }
+}
+
+/// Describes `err`, as returned by [`run`](crate::run), as a JSON object for
+/// the `--json` modes: a [`FileError`] as by [`FileError::to_json`], and any
+/// other error with just an `error` field.
+///
+/// # Examples
+///
+/// ```
+/// use std::error::Error;
+///
+/// let err: Box<dyn Error> = "Invalid regular expression".into();
+///
+/// assert_eq!(
+///     simple_grep::error_json(err.as_ref()),
+///     r#"{"error":"Invalid regular expression"}"#
+/// );
+/// ```
+pub fn error_json(err: &(dyn Error + 'static)) -> String {
+    match err.downcast_ref::<FileError>() {
+        Some(err) => err.to_json(),
+        None => format!(r#"{{"error":{}}}"#, json_string(&err.to_string())),
+    }
+}
+
+/// Invalid UTF-8 in a file searched with `--strict-utf8`.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::InvalidUtf8;
+///
+/// let err = InvalidUtf8 { offset: 15 };
+///
+/// assert_eq!(err.to_string(), "invalid UTF-8 at byte offset 15");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidUtf8 {
+    /// The offset of the first invalid byte from the start of the file.
+    pub offset: usize,
+}
+
+impl fmt::Display for InvalidUtf8 {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid UTF-8 at byte offset {}", self.offset)
+    }
+}
+
+impl Error for InvalidUtf8 {}
+
+/// Returns the [`InvalidUtf8`] error `err` is, or that the I/O error `err`
+/// wraps.
+pub(crate) fn invalid_utf8(err: &(dyn Error + 'static)) -> Option<InvalidUtf8> {
+    err.downcast_ref::<InvalidUtf8>()
+        .or_else(|| {
+            err.downcast_ref::<io::Error>()
+                .and_then(io::Error::get_ref)
+                .and_then(|inner| inner.downcast_ref::<InvalidUtf8>())
+        })
+        .copied()
+}
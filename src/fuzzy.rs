@@ -0,0 +1,153 @@
+//! Approximate matching within a bounded edit distance.
+
+use crate::Matcher;
+
+/// Matches the parts of a line that are within a maximum Levenshtein
+/// distance of the query, set with `--fuzzy` and `--max-distance`.
+///
+/// Distances are counted in characters: each inserted, deleted or
+/// substituted character costs one edit.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::{FuzzyMatcher, Matcher};
+///
+/// let matcher = FuzzyMatcher::new("receive", 1, false);
+///
+/// assert!(matcher.is_match("I didn't recive it"));
+/// assert!(!matcher.is_match("I didn't get it"));
+/// ```
+pub struct FuzzyMatcher {
+    query: Vec<char>,
+    max_distance: usize,
+    ignore_case: bool,
+}
+
+impl FuzzyMatcher {
+    /// Creates a matcher for `query` allowing up to `max_distance` edits.
+    pub fn new(query: &str, max_distance: usize, ignore_case: bool) -> FuzzyMatcher {
+        FuzzyMatcher {
+            query: query.chars().collect(),
+            max_distance,
+            ignore_case,
+        }
+    }
+
+    fn chars_eq(&self, a: char, b: char) -> bool {
+        a == b || (self.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
+    /// Returns the end (as a character index) of the first match in
+    /// `text[from..]`.
+    ///
+    /// This is Sellers' algorithm: a column of the edit-distance table is
+    /// computed for every character of the text, where a match may start
+    /// anywhere. With Ukkonen's cut-off only the rows whose distance can
+    /// still be within the bound are computed, so the work for a character
+    /// is abandoned as soon as the distance exceeds it.
+    ///
+    /// Once a match is found, it is extended for as long as that lowers the
+    /// distance, so `trust` finds `rust` rather than `rus`.
+    fn find_end(&self, text: &[char], from: usize) -> Option<usize> {
+        let (m, k) = (self.query.len(), self.max_distance);
+        // `column[i]` is the distance between `query[..i]` and the best
+        // substring ending at the current character, capped at `k + 1`.
+        let mut column: Vec<usize> = (0..=m).map(|i| i.min(k + 1)).collect();
+        let mut last_active = k;
+        let mut best: Option<(usize, usize)> = None;
+
+        for (j, &c) in text.iter().enumerate().skip(from) {
+            let rows = (last_active + 1).min(m);
+            column[rows] = column[rows].min(k + 1);
+
+            let mut diagonal = 0;
+            for i in 1..=rows {
+                let above = column[i];
+                column[i] = if self.chars_eq(self.query[i - 1], c) {
+                    diagonal
+                } else {
+                    1 + diagonal.min(above).min(column[i - 1])
+                };
+                diagonal = above;
+            }
+
+            last_active = rows;
+            while column[last_active] > k {
+                last_active -= 1;
+            }
+
+            if last_active == m {
+                match best {
+                    Some((_, distance)) if column[m] > distance => break,
+                    Some((_, distance)) if column[m] == distance => {}
+                    _ => best = Some((j + 1, column[m])),
+                }
+            } else if best.is_some() {
+                break;
+            }
+        }
+
+        best.map(|(end, _)| end)
+    }
+
+    /// Returns the start (as a character index, not before `from`) of the
+    /// closest match ending at `end`.
+    fn find_start(&self, text: &[char], from: usize, end: usize) -> usize {
+        let m = self.query.len();
+        // `row[i]` is the distance between the last `i` characters of the
+        // query and `text[start..end]`.
+        let mut row: Vec<usize> = (0..=m).collect();
+        let mut best = (end, row[m]);
+        let earliest = end.saturating_sub(m + self.max_distance).max(from);
+
+        for start in (earliest..end).rev() {
+            let mut diagonal = row[0];
+            row[0] += 1;
+
+            for i in 1..=m {
+                let above = row[i];
+                row[i] = if self.chars_eq(self.query[m - i], text[start]) {
+                    diagonal
+                } else {
+                    1 + diagonal.min(above).min(row[i - 1])
+                };
+                diagonal = above;
+            }
+
+            if row[m] < best.1 {
+                best = (start, row[m]);
+            }
+        }
+
+        best.0
+    }
+}
+
+impl Matcher for FuzzyMatcher {
+    fn find_spans(&self, line: &str) -> Vec<(usize, usize)> {
+        // A query no longer than the distance matches the empty string
+        // everywhere, which isn't worth reporting.
+        if self.query.len() <= self.max_distance {
+            return Vec::new();
+        }
+
+        let (offsets, text): (Vec<usize>, Vec<char>) = line.char_indices().unzip();
+        let byte = |index: usize| offsets.get(index).copied().unwrap_or(line.len());
+        let mut spans = Vec::new();
+        let mut from = 0;
+
+        while let Some(end) = self.find_end(&text, from) {
+            let start = self.find_start(&text, from, end);
+            spans.push((byte(start), byte(end)));
+            from = end.max(start + 1);
+        }
+
+        spans
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        let text: Vec<char> = line.chars().collect();
+        self.query.len() <= self.max_distance || self.find_end(&text, 0).is_some()
+    }
+}
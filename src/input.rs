@@ -0,0 +1,547 @@
+//! Opening input files, transparently decompressing them when needed.
+
+use std::borrow::Cow;
+use std::error::Error;
+use std::fs::File;
+use std::hash::{DefaultHasher, Hasher};
+use std::io::{self, BufRead, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::str;
+
+use crate::InvalidUtf8;
+
+/// Returns `true` if `path` is `-`, which stands for standard input.
+pub(crate) fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// Returns a hash of the raw contents of the file at `path`, for
+/// `--skip-duplicate-content`. It isn't cryptographic: it only tells files
+/// apart quickly.
+pub(crate) fn content_hash(path: &Path) -> io::Result<u64> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut hasher = DefaultHasher::new();
+
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(hasher.finish());
+        }
+
+        hasher.write(buf);
+        let len = buf.len();
+        reader.consume(len);
+    }
+}
+
+/// Capacity of the read buffer of input files unless `--buffer-size` is
+/// given, the same as the default of [`BufReader`].
+pub(crate) const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// The largest `--buffer-size`, far more than reading benefits from, so that
+/// a mistyped size is rejected rather than failing to allocate.
+pub(crate) const MAX_BUFFER_SIZE: usize = 1 << 30;
+
+/// Opens `path` like [`open_maybe_compressed`] with a read buffer of
+/// `capacity` bytes, or standard input for `-`, decompressed too with
+/// `decompress_stdin`.
+pub(crate) fn open(
+    path: &Path,
+    capacity: usize,
+    decompress_stdin: bool,
+) -> io::Result<Box<dyn BufRead>> {
+    open_at(path, capacity, 0, decompress_stdin)
+}
+
+/// Opens `path` like [`open`], positioned `offset` bytes into its
+/// contents, for `--seek-bytes`. Uncompressed files are seeked to the
+/// offset; compressed files and standard input are read up to it.
+pub(crate) fn open_at(
+    path: &Path,
+    capacity: usize,
+    offset: u64,
+    decompress_stdin: bool,
+) -> io::Result<Box<dyn BufRead>> {
+    if is_stdin(path) {
+        let mut reader: Box<dyn BufRead> = Box::new(io::stdin().lock());
+        if decompress_stdin {
+            reader = decompress(reader)?.unwrap_or_else(|reader| reader);
+        }
+        skip(&mut reader, offset)?;
+        Ok(reader)
+    } else {
+        open_with_capacity(path, capacity, offset)
+    }
+}
+
+/// Reads and discards the first `count` bytes of `reader`, or all of it if
+/// it's shorter.
+pub(crate) fn skip(reader: &mut dyn BufRead, count: u64) -> io::Result<()> {
+    io::copy(&mut reader.take(count), &mut io::sink())?;
+    Ok(())
+}
+
+/// Reads and discards the rest of the current line of `reader`, including
+/// its line break, and returns its length in bytes.
+pub(crate) fn skip_line(reader: &mut dyn BufRead) -> io::Result<usize> {
+    reader.skip_until(b'\n')
+}
+
+/// Reads the rest of `reader`, which starts at byte `offset` of the file,
+/// for `--strict-utf8`: invalid UTF-8 is an [`InvalidUtf8`] error with the
+/// offset of the first invalid byte.
+pub(crate) fn read_strict_utf8(
+    reader: &mut dyn BufRead,
+    offset: usize,
+) -> Result<String, Box<dyn Error>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    String::from_utf8(bytes).map_err(|err| {
+        let offset = offset + err.utf8_error().valid_up_to();
+        InvalidUtf8 { offset }.into()
+    })
+}
+
+/// Appends the next line of `reader`, which is at byte `offset` of the file,
+/// to `buffer` like [`BufRead::read_line`], but for `--strict-utf8`: invalid
+/// UTF-8 is an I/O error wrapping an [`InvalidUtf8`] error.
+pub(crate) fn read_strict_line(
+    reader: &mut dyn BufRead,
+    buffer: &mut String,
+    offset: usize,
+) -> io::Result<usize> {
+    let mut bytes = Vec::new();
+    let len = reader.read_until(b'\n', &mut bytes)?;
+
+    match str::from_utf8(&bytes) {
+        Ok(text) => {
+            buffer.push_str(text);
+            Ok(len)
+        }
+        Err(err) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            InvalidUtf8 {
+                offset: offset + err.valid_up_to(),
+            },
+        )),
+    }
+}
+
+/// Reads the paths separated by `separator` from `reader`, for
+/// `--stdin-paths`. Empty paths are skipped, and so is the carriage return
+/// before a line break.
+///
+/// # Errors
+///
+/// Returns an error if reading fails or a path isn't valid UTF-8.
+pub(crate) fn read_paths(reader: impl BufRead, separator: u8) -> io::Result<Vec<String>> {
+    let mut paths = Vec::new();
+
+    for path in reader.split(separator) {
+        let mut path = path?;
+
+        if separator == b'\n' && path.ends_with(b"\r") {
+            path.pop();
+        }
+
+        if !path.is_empty() {
+            let path = String::from_utf8(path)
+                .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+            paths.push(path);
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Runs `command`, a program followed by its arguments separated by
+/// whitespace, with `path` as its last argument and returns its output, for
+/// `--pre`. For standard input, `-`, the command reads standard input
+/// instead of being given a path. Its standard error is passed through.
+///
+/// # Errors
+///
+/// Returns an error if `command` is empty, can't be run or doesn't exit
+/// successfully.
+pub(crate) fn preprocess(command: &str, path: &Path) -> io::Result<Box<dyn BufRead>> {
+    let mut words = command.split_whitespace();
+    let program = words
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "the --pre command is empty"))?;
+
+    let mut process = Command::new(program);
+    process.args(words).stderr(Stdio::inherit());
+    if is_stdin(path) {
+        process.stdin(Stdio::inherit());
+    } else {
+        process.arg(path).stdin(Stdio::null());
+    }
+
+    let output = process
+        .output()
+        .map_err(|err| io::Error::new(err.kind(), format!("can't run '{command}': {err}")))?;
+
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "'{command}' failed with {}",
+            output.status
+        )));
+    }
+
+    Ok(Box::new(Cursor::new(output.stdout)))
+}
+
+/// Prepares `path` for opening on Windows, where paths longer than 260
+/// characters and some UNC paths (`\\server\share\...`) can't be opened as
+/// they are.
+///
+/// The path is made absolute and given the `\\?\` prefix, which lifts the
+/// length limit: `C:\logs\app.log` becomes `\\?\C:\logs\app.log` and
+/// `\\server\share\app.log` becomes `\\?\UNC\server\share\app.log`. Paths that
+/// already have a prefix, device paths and paths that aren't valid Unicode
+/// are returned unchanged.
+///
+/// On other platforms, `path` is always returned unchanged.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+///
+/// use simple_grep::long_path;
+///
+/// let path = long_path(Path::new(r"\\server\share\app.log"));
+///
+/// if cfg!(windows) {
+///     assert_eq!(path, Path::new(r"\\?\UNC\server\share\app.log"));
+/// } else {
+///     assert_eq!(path, Path::new(r"\\server\share\app.log"));
+/// }
+/// ```
+pub fn long_path(path: &Path) -> Cow<'_, Path> {
+    #[cfg(windows)]
+    if let Some(verbatim) = std::path::absolute(path)
+        .ok()
+        .and_then(|absolute| verbatim(absolute.to_str()?))
+    {
+        return Cow::Owned(verbatim.into());
+    }
+
+    Cow::Borrowed(path)
+}
+
+/// Adds the `\\?\` prefix to an absolute Windows path, returning `None` if it
+/// already has a prefix or isn't a drive or UNC path.
+#[cfg(windows)]
+fn verbatim(path: &str) -> Option<String> {
+    if path.starts_with(r"\\?\") || path.starts_with(r"\\.\") {
+        return None;
+    }
+
+    if let Some(share) = path.strip_prefix(r"\\") {
+        return Some(format!(r"\\?\UNC\{share}"));
+    }
+
+    let drive = path.as_bytes();
+    if drive.len() >= 3 && drive[0].is_ascii_alphabetic() && &drive[1..3] == b":\\" {
+        return Some(format!(r"\\?\{path}"));
+    }
+
+    None
+}
+
+/// A reader that retries reads interrupted by a signal (`EINTR`), which
+/// would otherwise fail with [`io::ErrorKind::Interrupted`].
+///
+/// The standard library already retries in `read_to_end` and `read_line`,
+/// but not in a single `read` such as the one behind `fill_buf`, which is
+/// how [`open_maybe_compressed`] looks at the start of a file. All reads of
+/// input files go through this reader.
+///
+/// # Examples
+///
+/// ```
+/// use std::io::Read;
+///
+/// use simple_grep::RetryReader;
+///
+/// let mut reader = RetryReader::new("text".as_bytes());
+/// let mut buf = [0; 4];
+///
+/// assert_eq!(reader.read(&mut buf)?, 4);
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub struct RetryReader<R> {
+    inner: R,
+}
+
+impl<R: Read> RetryReader<R> {
+    /// Wraps `inner`.
+    pub fn new(inner: R) -> RetryReader<R> {
+        RetryReader { inner }
+    }
+}
+
+impl<R: Read> Read for RetryReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            match self.inner.read(buf) {
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                result => return result,
+            }
+        }
+    }
+}
+
+impl<R: Seek> Seek for RetryReader<R> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.inner.seek(pos)
+    }
+}
+
+/// Magic bytes at the start of a gzip stream.
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+
+/// Magic bytes at the start of a bzip2 stream, followed by the block size,
+/// `1` to `9`.
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+/// Magic bytes after the block size of a bzip2 stream: those of the first
+/// block, or of the end of an empty stream.
+const BZIP2_BLOCK_MAGICS: [&[u8]; 2] = [
+    &[0x31, 0x41, 0x59, 0x26, 0x53, 0x59],
+    &[0x17, 0x72, 0x45, 0x38, 0x50, 0x90],
+];
+
+/// Magic bytes at the start of an xz stream.
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+/// Opens `path` for buffered reading, decompressing gzip, bzip2 and xz files.
+///
+/// The format is detected from the magic bytes at the start of the file, so
+/// it doesn't matter whether the file is named `.gz`, `.bz2` or `.xz`. Files
+/// in any other format are returned as they are, and so are files that only
+/// start like a compressed stream, such as a text file starting with `BZh`:
+/// those whose first block fails to decode.
+///
+/// Each decoder is only available when its cargo feature (`gzip`, `bzip2` or
+/// `xz`) is enabled.
+///
+/// # Errors
+///
+/// Returns an error if the file can't be opened, or if it is compressed in a
+/// format whose feature wasn't compiled in.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::io::Read;
+///
+/// use simple_grep::open_maybe_compressed;
+///
+/// let mut contents = String::new();
+/// open_maybe_compressed("logs/app.log.gz")?.read_to_string(&mut contents)?;
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn open_maybe_compressed(path: impl AsRef<Path>) -> io::Result<Box<dyn BufRead>> {
+    open_with_capacity(path.as_ref(), DEFAULT_BUFFER_SIZE, 0)
+}
+
+/// Opens `path` like [`open_maybe_compressed`], reading it `capacity` bytes
+/// at a time, and positioned `offset` bytes into its decompressed contents.
+fn open_with_capacity(path: &Path, capacity: usize, offset: u64) -> io::Result<Box<dyn BufRead>> {
+    let file = RetryReader::new(File::open(long_path(path))?);
+    let reader = BufReader::with_capacity(capacity, file);
+
+    match decompress(reader)? {
+        Ok(mut reader) => {
+            skip(&mut reader, offset)?;
+            Ok(reader)
+        }
+        Err(mut reader) => {
+            if offset > 0 {
+                reader.seek(SeekFrom::Start(offset))?;
+            }
+
+            Ok(Box::new(reader))
+        }
+    }
+}
+
+/// Returns a decoder for `reader` if it starts with the magic bytes of a
+/// gzip, bzip2 or xz stream, or gives `reader` back otherwise.
+///
+/// If the first block doesn't decode, the stream only looked compressed:
+/// `reader` is returned from its start, boxed, to be read as it is.
+fn decompress<R: BufRead + 'static>(mut reader: R) -> io::Result<Result<Box<dyn BufRead>, R>> {
+    let head = reader.fill_buf()?;
+
+    if head.starts_with(GZIP_MAGIC) {
+        gzip(reader).map(Ok)
+    } else if is_bzip2(head) {
+        bzip2(reader).map(Ok)
+    } else if head.starts_with(XZ_MAGIC) {
+        xz(reader).map(Ok)
+    } else {
+        Ok(Err(reader))
+    }
+}
+
+/// Returns `true` if `head` starts with the header of a bzip2 stream: the
+/// magic bytes, the block size and the magic bytes of the first block.
+fn is_bzip2(head: &[u8]) -> bool {
+    let Some(rest) = head.strip_prefix(BZIP2_MAGIC) else {
+        return false;
+    };
+
+    match rest.split_first() {
+        Some((block_size, rest)) => {
+            (b'1'..=b'9').contains(block_size)
+                && BZIP2_BLOCK_MAGICS
+                    .iter()
+                    .any(|magic| rest.starts_with(magic))
+        }
+        None => false,
+    }
+}
+
+/// Decodes `reader` with the decoder built by `new`, whose input is given
+/// back by `into_inner`, or, if the first block fails to decode, returns
+/// `reader` from its start to be read as it is.
+#[cfg(any(feature = "gzip", feature = "bzip2", feature = "xz"))]
+fn decode_or_rewind<R: BufRead + 'static, D: Read + 'static>(
+    reader: R,
+    new: impl FnOnce(Rewind<R>) -> D,
+    get_mut: fn(&mut D) -> &mut Rewind<R>,
+    into_inner: fn(D) -> Rewind<R>,
+) -> Box<dyn BufRead> {
+    let mut decoder = BufReader::new(new(Rewind::new(reader)));
+
+    if decoder.fill_buf().is_ok() {
+        get_mut(decoder.get_mut()).stop();
+        Box::new(decoder)
+    } else {
+        Box::new(into_inner(decoder.into_inner()).rewind())
+    }
+}
+
+/// Reads `inner`, keeping a copy of what is read until [`Rewind::stop`], so
+/// that it can be read again from the start.
+#[cfg(any(feature = "gzip", feature = "bzip2", feature = "xz"))]
+struct Rewind<R> {
+    inner: R,
+    /// The bytes read so far, until `stop`.
+    read: Option<Vec<u8>>,
+}
+
+#[cfg(any(feature = "gzip", feature = "bzip2", feature = "xz"))]
+impl<R: BufRead> Rewind<R> {
+    fn new(inner: R) -> Rewind<R> {
+        Rewind {
+            inner,
+            read: Some(Vec::new()),
+        }
+    }
+
+    /// Stops keeping a copy of what is read.
+    fn stop(&mut self) {
+        self.read = None;
+    }
+
+    /// Returns a reader of `inner` from the start, with the bytes read so
+    /// far first.
+    fn rewind(self) -> impl BufRead {
+        Cursor::new(self.read.unwrap_or_default()).chain(self.inner)
+    }
+}
+
+#[cfg(any(feature = "gzip", feature = "bzip2", feature = "xz"))]
+impl<R: BufRead> Read for Rewind<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let available = self.fill_buf()?;
+        let len = available.len().min(buf.len());
+        buf[..len].copy_from_slice(&available[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+#[cfg(any(feature = "gzip", feature = "bzip2", feature = "xz"))]
+impl<R: BufRead> BufRead for Rewind<R> {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        self.inner.fill_buf()
+    }
+
+    fn consume(&mut self, amount: usize) {
+        if let Some(read) = &mut self.read
+            // The buffer was filled before, so this doesn't read.
+            && let Ok(buffer) = self.inner.fill_buf()
+        {
+            read.extend_from_slice(&buffer[..amount.min(buffer.len())]);
+        }
+
+        self.inner.consume(amount);
+    }
+}
+
+#[cfg(feature = "gzip")]
+fn gzip(reader: impl BufRead + 'static) -> io::Result<Box<dyn BufRead>> {
+    use flate2::bufread::MultiGzDecoder;
+
+    Ok(decode_or_rewind(
+        reader,
+        MultiGzDecoder::new,
+        MultiGzDecoder::get_mut,
+        MultiGzDecoder::into_inner,
+    ))
+}
+
+#[cfg(not(feature = "gzip"))]
+fn gzip(_: impl BufRead) -> io::Result<Box<dyn BufRead>> {
+    Err(unsupported("gzip"))
+}
+
+#[cfg(feature = "bzip2")]
+fn bzip2(reader: impl BufRead + 'static) -> io::Result<Box<dyn BufRead>> {
+    use bzip2::bufread::MultiBzDecoder;
+
+    Ok(decode_or_rewind(
+        reader,
+        MultiBzDecoder::new,
+        MultiBzDecoder::get_mut,
+        MultiBzDecoder::into_inner,
+    ))
+}
+
+#[cfg(not(feature = "bzip2"))]
+fn bzip2(_: impl BufRead) -> io::Result<Box<dyn BufRead>> {
+    Err(unsupported("bzip2"))
+}
+
+#[cfg(feature = "xz")]
+fn xz(reader: impl BufRead + 'static) -> io::Result<Box<dyn BufRead>> {
+    use xz2::bufread::XzDecoder;
+
+    Ok(decode_or_rewind(
+        reader,
+        XzDecoder::new_multi_decoder,
+        XzDecoder::get_mut,
+        XzDecoder::into_inner,
+    ))
+}
+
+#[cfg(not(feature = "xz"))]
+fn xz(_: impl BufRead) -> io::Result<Box<dyn BufRead>> {
+    Err(unsupported("xz"))
+}
+
+/// Error for a compressed file whose decoder wasn't compiled in.
+#[cfg(not(all(feature = "gzip", feature = "bzip2", feature = "xz")))]
+fn unsupported(format: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("{format} compressed input requires the `{format}` feature"),
+    )
+}
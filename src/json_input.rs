@@ -0,0 +1,35 @@
+//! Matching only the string values of JSON lines for `--json-input`.
+
+/// Returns the string values of the JSON object `line`, including those of
+/// nested objects and arrays but not the keys, or `None` if `line` isn't a
+/// JSON object.
+#[cfg(feature = "json")]
+pub(crate) fn string_values(line: &str) -> Option<Vec<String>> {
+    use serde_json::Value;
+
+    fn collect(value: Value, values: &mut Vec<String>) {
+        match value {
+            Value::String(text) => values.push(text),
+            Value::Array(items) => items.into_iter().for_each(|item| collect(item, values)),
+            Value::Object(fields) => fields
+                .into_iter()
+                .for_each(|(_, field)| collect(field, values)),
+            Value::Null | Value::Bool(_) | Value::Number(_) => {}
+        }
+    }
+
+    let object @ Value::Object(_) = serde_json::from_str(line).ok()? else {
+        return None;
+    };
+
+    let mut values = Vec::new();
+    collect(object, &mut values);
+    Some(values)
+}
+
+/// Without the `json` feature, [`Query`](crate::search::Query) rejects
+/// `--json-input` before any line is parsed.
+#[cfg(not(feature = "json"))]
+pub(crate) fn string_values(_: &str) -> Option<Vec<String>> {
+    None
+}
@@ -4,12 +4,18 @@
 //! that contain a specific query string in a given text file, similar to Unix `grep`.
 //!
 //! It supports both case-sensitive and case-insensitive search based on the
-//! `IGNORE_CASE` environment variable.
+//! `-i` option or the `IGNORE_CASE` environment variable.
 //!
 //! ## Example
 //!
 //! ```bash
-//! cargo run -- <query> <file_path>
+//! cargo run -- <query> <file_path>...
+//! ```
+//!
+//! Search a directory recursively:
+//!
+//! ```bash
+//! cargo run -- -r <query> <directory>
 //! ```
 //!
 //! Case-insensitive search:
@@ -18,94 +24,148 @@
 //! IGNORE_CASE=1 cargo run -- <query> <file_path>
 //! ```
 //!
+//! Default options can be set with the `MINIGREP_OPTIONS` environment variable:
+//!
+//! ```bash
+//! MINIGREP_OPTIONS="-i -n" cargo run -- <query> <file_path>
+//! ```
+//!
+//! ## Compressed Input
+//!
+//! Files compressed with gzip are searched transparently. Support for bzip2
+//! and xz is available through the `bzip2` and `xz` cargo features:
+//!
+//! ```bash
+//! cargo run --features bzip2,xz -- <query> <file_path>
+//! ```
+//!
 //! ## Crate Structure
 //!
 //! - [`Config`] struct handles argument parsing and configuration.
-//! - [`run`] function executes the main logic.
-//! - [`search`] and [`search_case_insensitive`] perform line matching.
+//! - [`run`] function executes the main logic and returns a [`Summary`].
+//! - [`help`] lists the options, and [`list_options`] in a machine-readable
+//!   form; [`version`] describes the version and the compiled-in features.
+//! - [`ColorChoice`] controls highlighting of matches.
+//! - [`CancelFlag`] stops a search early, such as on Ctrl-C.
+//! - [`Clock`] tells the time of the matches for `--timestamp`.
+//! - [`clipboard_query`] reads the query of `--pattern-clipboard` from a
+//!   [`ClipboardSource`] such as the [`SystemClipboard`].
+//! - [`FileError`] reports a file that couldn't be searched, such as one with
+//!   [`InvalidUtf8`] with `--strict-utf8`; [`error_json`] describes errors as
+//!   JSON for the `--json` modes.
+//! - [`OutputEncoding`] selects the encoding of the output.
+//! - [`Normalization`] selects the Unicode normalization form of `--normalize`.
+//! - [`Template`] renders matches in a custom `--format`; [`split_output_path`]
+//!   names the output file of an input file with `--split-output`.
+//! - [`Matcher`] finds occurrences of the query in a line; [`FuzzyMatcher`]
+//!   implements `--fuzzy` matching and `RegexMatcher` (with the `regex`
+//!   feature) `--regex` matching.
+//! - [`open_maybe_compressed`] opens plain, gzip, bzip2 and xz input files;
+//!   [`long_path`] lets it open long and UNC paths on Windows, and
+//!   [`RetryReader`] retries reads interrupted by signals.
+//! - [`search`], [`search_case_insensitive`] and [`search_ascii_case_insensitive`]
+//!   perform line matching.
+//! - [`group_count`] tallies identical matching lines.
 
-use std::env;
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 use std::error::Error;
 use std::fs;
+use std::io::{self, BufRead, BufWriter, Read, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
 
-/// Holds the configuration parameters for the SimpleGrep application.
-///
-/// This struct is created using the [`Config::build`] method,
-/// which parses command-line arguments and reads the `IGNORE_CASE`
-/// environment variable to determine if the search should be case-insensitive.
-pub struct Config {
-    /// The string to search for in the file.
-    pub query: String,
-
-    /// The path to the input file to search.
-    pub file_path: String,
-
-    /// A flag that determines whether the search should be case-insensitive.
-    /// This is set based on the `IGNORE_CASE` environment variable.
-    pub ignore_case: bool,
-}
+mod binary;
+mod cancel;
+mod clipboard;
+mod clock;
+mod config;
+mod encoding;
+mod error;
+mod fuzzy;
+mod input;
+mod json_input;
+mod matcher;
+mod min_length;
+#[cfg(feature = "aho-corasick")]
+mod multi;
+mod normalize;
+mod output;
+mod pager;
+#[cfg(feature = "regex")]
+mod pattern;
+mod sample;
+mod search;
+mod squeeze;
+mod stats;
+mod types;
+mod variants;
+mod walk;
+mod word;
 
-impl Config {
-    /// Parses command-line arguments and builds a `Config`.
-    ///
-    /// # Arguments
-    ///
-    /// * `args` - An iterator over command-line arguments, typically from `env::args()`.
-    ///
-    /// # Returns
-    ///
-    /// * `Ok(Config)` if both query and file path are provided.
-    /// * `Err(&str)` with an error message if arguments are missing.
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use simple_grep::Config;
-    ///
-    /// let args = vec![
-    ///     String::from("simple_grep"), // normally the binary name
-    ///     String::from("Rust"),
-    ///     String::from("input.txt"),
-    /// ];
-    ///
-    /// let config = Config::build(args.into_iter()).unwrap();
-    ///
-    /// assert_eq!(config.query, "Rust");
-    /// assert_eq!(config.file_path, "input.txt");
-    /// ```
-    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
-        args.next(); // skip program name
-
-        let query = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a query string"),
-        };
+pub use cancel::{CancelFlag, Deadline};
+pub use clipboard::{ClipboardSource, SystemClipboard, clipboard_query};
+pub use clock::Clock;
+pub use config::{Config, OPTIONS_ENV, help, list_options};
+pub use encoding::OutputEncoding;
+use encoding::Utf16Writer;
+pub use error::{FileError, InvalidUtf8, error_json};
+pub use fuzzy::FuzzyMatcher;
+pub use input::{RetryReader, long_path, open_maybe_compressed};
+pub use matcher::Matcher;
+pub use normalize::Normalization;
+use normalize::normalize;
+use output::{
+    COLOR_RESET, CSV_HEADER, DEFAULT_BUCKET_SIZE, JsonArray, NoTrailingNewline, OutputFile, Printer,
+};
+pub use output::{ColorChoice, Template, split_output_path};
+use pager::Pager;
+#[cfg(feature = "regex")]
+pub use pattern::RegexMatcher;
+use sample::Reservoir;
+use search::{Line, OwnedLine, Query, lines, strip_bom, trim_line_terminator};
+pub use search::{group_count, search, search_ascii_case_insensitive, search_case_insensitive};
+use stats::Stats;
+use types::FileTypes;
 
-        let file_path = match args.next() {
-            Some(arg) => arg,
-            None => return Err("Didn't get a file path"),
-        };
-
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
-
-        Ok(Config {
-            query,
-            file_path,
-            ignore_case,
-        })
-    }
-}
-
-/// Executes the main logic of SimpleGrep: reads the file, searches for the query,
+/// Executes the main logic of SimpleGrep: reads the files, searches for the query,
 /// and prints matching lines to stdout.
 ///
+/// Compressed files are decompressed first, see [`open_maybe_compressed`],
+/// and a path of `-` reads standard input. When more than one file is
+/// searched, each line is prefixed with the name of its file. Files that
+/// can't be read while searching a directory recursively are reported on
+/// stderr and skipped; with `quiet_errors`, all files that can't be read are
+/// skipped without a message. With `dedupe_files`, a file reached through several
+/// paths is only searched the first time. With `threads`, several files are
+/// searched in parallel, with the same output. With `count_total`, the number of
+/// selected lines across all files is printed last. With `sample`, only a
+/// random sample of the matches is printed once all files have been searched.
+/// With `json_array`, the matches of all files form a single JSON array.
+/// The output is encoded with the `output_encoding` and written to stdout,
+/// or to the `output` file. With `split_output`, the
+/// output of each file is written to a file of its own instead. If the query
+/// names a file and the files to search don't exist, a warning that the
+/// arguments may be swapped is printed on stderr first. With
+/// `report_unmatched`, the queries that no selected line matched are reported
+/// on stderr.
+///
 /// # Arguments
 ///
 /// * `config` - A `Config` object containing the search parameters.
 ///
+/// # Returns
+///
+/// A [`Summary`] of the search, from which `main` derives the exit status.
+///
 /// # Errors
 ///
-/// Returns a boxed `dyn Error` if reading the file fails.
+/// Returns a boxed `dyn Error` if the query is an invalid regular
+/// expression, or if reading one of the given files, or writing to stdout,
+/// fails.
 ///
 /// # Examples
 ///
@@ -114,96 +174,1592 @@ impl Config {
 ///
 /// let config = Config {
 ///     query: String::from("Rust"),
-///     file_path: String::from("input.txt"),
+///     file_paths: vec![String::from("input.txt")],
 ///     ignore_case: false,
+///     ..Config::default()
 /// };
 ///
 /// if let Err(e) = run(config) {
 ///     eprintln!("Application error: {e}");
 /// }
 /// ```
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    let contents = fs::read_to_string(config.file_path)?;
+pub fn run(config: Config) -> Result<Summary, Box<dyn Error>> {
+    if config.arguments_look_swapped() {
+        eprintln!(
+            "simple_grep: warning: the query '{}' is a file and the files to search don't \
+             exist; the arguments may be swapped (the query comes first)",
+            config.query
+        );
+    }
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
+    if config.compress && config.output.is_none() {
+        return Err("--compress requires --output".into());
+    }
+
+    let mut file = match &config.output {
+        Some(path) => {
+            let file = create_output(Path::new(path), config.append)?;
+            Some(OutputFile::new(file, config.compress)?)
+        }
+        None => None,
+    };
+    let mut pager = Pager::spawn(&config);
+    let stdout: Box<dyn Write> = match (&mut file, &mut pager) {
+        (Some(file), _) => Box::new(file),
+        (None, Some(pager)) => Box::new(pager),
+        (None, None) => Box::new(io::stdout().lock()),
+    };
+    let stdout = BufWriter::new(stdout);
+    let mut out: Box<dyn Write + '_> = match config.output_encoding {
+        OutputEncoding::Utf8 => Box::new(stdout),
+        OutputEncoding::Utf16Le => Box::new(Utf16Writer::new(stdout, true)),
+        OutputEncoding::Utf16Be => Box::new(Utf16Writer::new(stdout, false)),
+    };
+    if config.no_trailing_newline {
+        out = Box::new(NoTrailingNewline::new(out));
+    }
+
+    if config.csv_header {
+        writeln!(out, "{CSV_HEADER}")?;
+    }
+
+    let deadline = config
+        .max_time
+        .map(|budget| config.cancel.cancel_after(budget));
+    let start = Instant::now();
+    let total = if config.json_array {
+        let mut array = JsonArray::new(&mut out);
+        let total = search_paths(&config, &mut array)?;
+        array.finish()?;
+        total
     } else {
-        search(&config.query, &contents)
+        search_paths(&config, &mut out)?
     };
 
-    for line in results {
-        println!("{line}");
+    if config.length_histogram {
+        let bucket_size = config.bucket_size.unwrap_or(DEFAULT_BUCKET_SIZE);
+        output::write_histogram(&mut out, &total.lengths, bucket_size)?;
+    }
+
+    if config.count_total {
+        writeln!(out, "total:{}", total.selected)?;
+    }
+
+    if let Some(percentile) = config.percentile {
+        output::write_percentile(&mut out, "total", &total, percentile)?;
+    }
+
+    if config.pattern_file_coverage {
+        for (index, pattern) in config.queries().iter().enumerate() {
+            let files = total.pattern_files.get(index).copied().unwrap_or(0);
+            writeln!(out, "{pattern}: {files}/{}", total.files)?;
+        }
+    }
+
+    if config.cooccurrence {
+        let queries = config.queries();
+
+        for (first, a) in queries.iter().enumerate() {
+            for (second, b) in queries.iter().enumerate().skip(first + 1) {
+                let lines = total.pattern_pairs.get(&(first, second)).unwrap_or(&0);
+                writeln!(out, "{a} & {b}: {lines}")?;
+            }
+        }
+    }
+
+    if config.summary {
+        writeln!(out, "total: {}", output::summary(&total))?;
+    }
+
+    let timed_out = deadline.is_some_and(|deadline| deadline.expired());
+    if config.cancel.is_cancelled() && output::use_color(&config) {
+        write!(out, "{COLOR_RESET}")?;
+    }
+
+    out.flush()?;
+    drop(out);
+
+    if let Some(file) = file {
+        file.finish()?;
+    }
+
+    // Quitting the pager early stops the search, but isn't an interruption.
+    let pager_closed = match pager {
+        Some(pager) => pager.finish()?,
+        None => false,
+    };
+    let cancelled = config.cancel.is_cancelled() && !timed_out && !pager_closed;
+
+    if let (true, Some(budget)) = (timed_out, config.max_time) {
+        eprintln!(
+            "simple_grep: stopped early after the --max-time of {}s, the results are incomplete",
+            budget.as_secs_f64()
+        );
+    }
+
+    if config.report_unmatched {
+        for (index, pattern) in config.queries().iter().enumerate() {
+            if total
+                .pattern_matches
+                .get(index)
+                .is_none_or(|&count| count == 0)
+            {
+                eprintln!("simple_grep: no lines matched pattern: {pattern}");
+            }
+        }
+    }
+
+    if config.measure_throughput {
+        let elapsed = start.elapsed();
+        eprintln!(
+            "throughput: {:.2} MB/s ({} bytes in {:.3}s)",
+            total.megabytes_per_second(elapsed),
+            total.bytes,
+            elapsed.as_secs_f64()
+        );
+    }
+
+    if config.summary_json {
+        eprintln!("{}", output::summary_json(&total, start.elapsed()));
+    }
+
+    Ok(Summary {
+        selected: total.selected,
+        failed: total.failed,
+        cancelled,
+        timed_out,
+    })
+}
+
+/// What [`run`] found.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    /// Number of selected lines across all files, i.e. with
+    /// `whole_file_match`, the number of files that matched.
+    pub selected: usize,
+    /// Number of files that couldn't be read and were skipped.
+    pub failed: usize,
+    /// Whether the search was stopped early through the `cancel` flag, other
+    /// than by `max_time` or by quitting the pager.
+    pub cancelled: bool,
+    /// Whether the search was stopped early because `max_time` elapsed.
+    pub timed_out: bool,
+}
+
+/// Searches all paths of `config`, returning the statistics of all files
+/// added up.
+fn search_paths(config: &Config, out: &mut dyn Write) -> Result<Stats, Box<dyn Error>> {
+    let query = Query::new(config)?;
+
+    let read_paths = if config.stdin_paths {
+        let separator = if config.null { b'\0' } else { b'\n' };
+        input::read_paths(io::stdin().lock(), separator)?
+    } else {
+        Vec::new()
+    };
+    let paths: Vec<&String> = config.file_paths.iter().chain(&read_paths).collect();
+
+    let show_filename = config.recursive || paths.len() > 1;
+    let types = FileTypes::new(config)?;
+    let files = files_to_search(config, &types, &paths);
+
+    if let Some(only_in) = &config.only_in {
+        return search_only_in(config, &query, Path::new(only_in), &files, out);
+    }
+
+    if let Some(dir) = &config.split_by_pattern {
+        create_pattern_outputs(config, Path::new(dir))?;
+    }
+
+    let threads = config.threads.unwrap_or(1);
+
+    if threads > 1
+        && config.sample.is_none()
+        && config.split_by_pattern.is_none()
+        && config.max_total.is_none()
+        && !config.line_buffered
+        && !config.parallel_within_file
+        && !config.continuous_lineno
+    {
+        return search_parallel(config, &query, &files, show_filename, threads, out);
+    }
+
+    // Across all files, for `--count-total` and `--measure-throughput`.
+    let mut total = Stats::default();
+    let mut sample = config.sample.map(|size| Reservoir::new(size, config.seed));
+
+    for (file, explicit) in &files {
+        // How many more lines may be selected with `--max-total`.
+        let max_selected = config
+            .max_total
+            .map(|max| max.saturating_sub(total.selected));
+
+        if config.cancel.is_cancelled() || max_selected == Some(0) {
+            break;
+        }
+
+        // The lines of the files before, numbered on with `--continuous-lineno`.
+        let first_line = if config.continuous_lineno {
+            total.lines
+        } else {
+            0
+        };
+
+        let mut search = |out: &mut dyn Write| {
+            search_file(
+                config,
+                &query,
+                file,
+                show_filename,
+                &mut sample,
+                max_selected,
+                first_line,
+                out,
+            )
+        };
+
+        let result = if config.split_output.is_some() {
+            let mut buffer = Vec::new();
+            let result = search(&mut buffer);
+            write_file_output(config, file, &buffer, out)?;
+            result
+        } else {
+            search(out)
+        };
+
+        match result {
+            Ok(stats) => total.add_file(stats),
+            Err(err) => skip_failed(config, file, *explicit, err, &mut total)?,
+        }
+    }
+
+    for rendered in sample.into_iter().flat_map(Reservoir::into_items) {
+        out.write_all(&rendered)?;
     }
 
+    Ok(total)
+}
+
+/// Writes the `output` of searching `file` to `out`, or to its own file with
+/// `split_output`, creating the directories it is in as needed. With
+/// `append`, the output is appended to the file.
+fn write_file_output(
+    config: &Config,
+    file: &Path,
+    output: &[u8],
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let Some(dir) = &config.split_output else {
+        return out.write_all(output);
+    };
+
+    if output.is_empty() && config.skip_empty {
+        return Ok(());
+    }
+
+    let path = split_output_path(Path::new(dir), file);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    create_output(&path, config.append)?.write_all(output)
+}
+
+/// Creates `dir` and the `--split-by-pattern` files in it, empty unless
+/// `append`, for the selected lines of each file to be appended to them.
+fn create_pattern_outputs(config: &Config, dir: &Path) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+
+    for index in 0..config.queries().len() {
+        create_output(&pattern_output_path(dir, index), config.append)?;
+    }
     Ok(())
 }
 
-/// Performs a **case-sensitive** search of `query` in `contents`.
-///
-/// # Arguments
-///
-/// * `query` - The search string.
-/// * `contents` - The contents of the file as a string slice.
+/// The `--split-by-pattern` file below `dir` for the query at `index`,
+/// numbered from 1.
+fn pattern_output_path(dir: &Path, index: usize) -> PathBuf {
+    dir.join(format!("{}.txt", index + 1))
+}
+
+/// Creates the output file `path`, or opens it to append to it with
+/// `append` (`--append`).
+fn create_output(path: &Path, append: bool) -> io::Result<fs::File> {
+    fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .append(append)
+        .truncate(!append)
+        .open(path)
+}
+
+/// Handles `err` from searching `file`: with `fail_fast` any file, else a
+/// file given explicitly, or with `strict_utf8` any file with invalid UTF-8,
+/// fails the search with a [`FileError`]. Other files, and with
+/// `quiet_errors` all files but those of `fail_fast`, are counted in `total`
+/// and skipped, with a message on stderr unless `quiet_errors`, which is a
+/// JSON object with `json`.
+fn skip_failed(
+    config: &Config,
+    file: &Path,
+    explicit: bool,
+    err: Box<dyn Error>,
+    total: &mut Stats,
+) -> Result<(), Box<dyn Error>> {
+    let fatal = explicit || (config.strict_utf8 && error::invalid_utf8(err.as_ref()).is_some());
+    let err = FileError {
+        path: file.to_path_buf(),
+        source: err,
+    };
+
+    if (fatal && !config.quiet_errors) || config.fail_fast {
+        return Err(err.into());
+    }
+
+    if !config.quiet_errors {
+        if config.json {
+            eprintln!("{}", err.to_json());
+        } else {
+            eprintln!("simple_grep: {err}");
+        }
+    }
+
+    total.failed += 1;
+    Ok(())
+}
+
+/// Searches `only_in` for `--only-in`, printing only the selected lines that
+/// aren't also selected lines of `files`. With `symmetric`, `files` are
+/// searched next, printing only the selected lines that aren't selected
+/// lines of `only_in`.
+fn search_only_in(
+    config: &Config,
+    query: &Query,
+    only_in: &Path,
+    files: &[(PathBuf, bool)],
+    out: &mut dyn Write,
+) -> Result<Stats, Box<dyn Error>> {
+    let mut others = HashSet::new();
+
+    for (file, _) in files {
+        others.extend(selected_lines(config, query, file)?);
+    }
+
+    let in_only_in = config
+        .symmetric
+        .then(|| selected_lines(config, query, only_in))
+        .transpose()?;
+
+    let show_filename = config.symmetric;
+    let mut total = Stats::default();
+    total.add_file(search_file(
+        config,
+        &Query::new(config)?.skipping(others),
+        only_in,
+        show_filename,
+        &mut None,
+        None,
+        0,
+        out,
+    )?);
+
+    if let Some(in_only_in) = in_only_in {
+        let query = Query::new(config)?.skipping(in_only_in);
+
+        for (file, _) in files {
+            total.add_file(search_file(
+                config,
+                &query,
+                file,
+                show_filename,
+                &mut None,
+                None,
+                0,
+                out,
+            )?);
+        }
+    }
+
+    Ok(total)
+}
+
+/// Returns the keys of the lines of `path` that `query` selects, see
+/// [`Query::line_key`].
+fn selected_lines(
+    config: &Config,
+    query: &Query,
+    path: &Path,
+) -> Result<HashSet<String>, Box<dyn Error>> {
+    let mut contents = String::new();
+    input::open(path, config.buffer_capacity(), config.decompress_stdin)?
+        .read_to_string(&mut contents)?;
+
+    let name = path.display().to_string();
+    let mut searcher = FileSearcher::new(config, query, &name, false);
+
+    Ok(lines(&contents)
+        .filter(|line| searcher.selects(line.text))
+        .map(|line| query.line_key(line.text).into_owned())
+        .collect())
+}
+
+/// Returns the files to search for `paths`, in order, each with whether it
+/// was given explicitly rather than found while searching a directory
+/// recursively, in which case it must be one of the `types`. With
+/// `skip_duplicate_content`, files with the same contents as an earlier one
+/// are left out. With `max_files`, only the first ones are returned, and
+/// with `reverse_file_order` in the reverse order. The empty ones are noted
+/// on stderr with `report_empty`.
+fn files_to_search(config: &Config, types: &FileTypes, paths: &[&String]) -> Vec<(PathBuf, bool)> {
+    // Canonical paths of the files seen so far, for `--dedupe-files`.
+    let mut searched = HashSet::new();
+    let mut first_visit = |path: &Path| {
+        !config.dedupe_files
+            || input::is_stdin(path)
+            || fs::canonicalize(path).map_or(true, |canonical| searched.insert(canonical))
+    };
+    // The first file with each content hash, for `--skip-duplicate-content`.
+    // Files that can't be read are kept, to report the error when searching.
+    let mut contents: HashMap<u64, PathBuf> = HashMap::new();
+    let mut new_content = |path: &Path| {
+        if !config.skip_duplicate_content || input::is_stdin(path) {
+            return true;
+        }
+
+        let Ok(hash) = input::content_hash(path) else {
+            return true;
+        };
+
+        match contents.get(&hash) {
+            Some(first) => {
+                eprintln!(
+                    "simple_grep: {}: skipped, same content as {}",
+                    path.display(),
+                    first.display()
+                );
+                false
+            }
+            None => {
+                contents.insert(hash, path.to_path_buf());
+                true
+            }
+        }
+    };
+    let mut files = Vec::new();
+
+    for path in paths {
+        if config.cancel.is_cancelled() || config.max_files.is_some_and(|max| files.len() >= max) {
+            break;
+        }
+
+        let path = Path::new(path);
+
+        if config.recursive && path.is_dir() {
+            // Stop walking once there are `--max-files` files, so the rest of
+            // the tree is neither read nor hashed.
+            walk::visit_files(
+                path,
+                config.follow,
+                &config.exclude_dir,
+                config.glob_ignore_case,
+                &config.cancel,
+                &mut |file| {
+                    if types.matches(&file) && first_visit(&file) && new_content(&file) {
+                        files.push((file, false));
+                    }
+                    config.max_files.is_none_or(|max| files.len() < max)
+                },
+            );
+        } else if first_visit(path) && new_content(path) {
+            files.push((path.to_path_buf(), true));
+        }
+    }
+
+    if let Some(max) = config.max_files {
+        files.truncate(max);
+    }
+
+    if config.reverse_file_order {
+        files.reverse();
+    }
+
+    if config.report_empty {
+        for (file, _) in &files {
+            let empty = !input::is_stdin(file)
+                && fs::metadata(file).is_ok_and(|meta| meta.is_file() && meta.len() == 0);
+            if empty {
+                eprintln!("simple_grep: {}: empty file", file.display());
+            }
+        }
+    }
+
+    files
+}
+
+/// Searches `files` on `threads` threads (`--threads`), returning the
+/// statistics of all files added up.
 ///
-/// # Returns
+/// Each file is searched on its own by one thread, into a buffer, so its line
+/// numbers and offsets are the same as when searching it alone. The buffers
+/// are written to `out` in the order of `files`, as soon as all the files
+/// before them are done, so the output is identical to a single-threaded
+/// search.
+fn search_parallel(
+    config: &Config,
+    query: &Query,
+    files: &[(PathBuf, bool)],
+    show_filename: bool,
+    threads: usize,
+    out: &mut dyn Write,
+) -> Result<Stats, Box<dyn Error>> {
+    let next = AtomicUsize::new(0);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..threads.min(files.len()) {
+            let (next, sender) = (&next, sender.clone());
+
+            scope.spawn(move || {
+                while !config.cancel.is_cancelled() {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    let Some((file, _)) = files.get(index) else {
+                        break;
+                    };
+
+                    let mut buffer = Vec::new();
+                    let result = search_file(
+                        config,
+                        query,
+                        file,
+                        show_filename,
+                        &mut None,
+                        None,
+                        0,
+                        &mut buffer,
+                    )
+                    // Errors are sent as text, but invalid UTF-8 has to stay
+                    // recognizable for `--strict-utf8`.
+                    .map_err(|err| -> Box<dyn Error + Send + Sync> {
+                        match error::invalid_utf8(err.as_ref()) {
+                            Some(invalid) => Box::new(invalid),
+                            None => err.to_string().into(),
+                        }
+                    });
+
+                    // The receiver is only gone once the search has failed.
+                    if sender.send((index, buffer, result)).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        let mut total = Stats::default();
+        // Files that were done before all the files preceding them, by index.
+        let mut pending = BTreeMap::new();
+        let mut written = 0;
+
+        for (index, buffer, result) in receiver {
+            pending.insert(index, (buffer, result));
+
+            while let Some((buffer, result)) = pending.remove(&written) {
+                let (file, explicit) = &files[written];
+                write_file_output(config, file, &buffer, out)?;
+
+                match result {
+                    Ok(stats) => total.add_file(stats),
+                    Err(err) => skip_failed(config, file, *explicit, err, &mut total)?,
+                }
+
+                written += 1;
+            }
+        }
+
+        Ok(total)
+    })
+}
+
+/// Optional cargo features, and whether each was compiled in.
+const FEATURES: &[(&str, bool)] = &[
+    ("gzip", cfg!(feature = "gzip")),
+    ("regex", cfg!(feature = "regex")),
+    ("aho-corasick", cfg!(feature = "aho-corasick")),
+    ("bzip2", cfg!(feature = "bzip2")),
+    ("clipboard", cfg!(feature = "clipboard")),
+    ("ctrlc", cfg!(feature = "ctrlc")),
+    ("json", cfg!(feature = "json")),
+    ("normalize", cfg!(feature = "normalize")),
+    ("xz", cfg!(feature = "xz")),
+];
+
+/// Returns the version of SimpleGrep and the optional cargo features it was
+/// built with, as printed by `--version`.
 ///
-/// A vector of lines that contain the query.
+/// Each feature is listed with `+` if it was compiled in and `-` otherwise.
 ///
 /// # Examples
 ///
 /// ```
-/// use simple_grep::search;
-///
-/// let query = "duct";
-/// let contents = "\
-/// Rust:
-/// safe, fast, productive.
-/// Pick three.
-/// Duct tape.";
+/// let version = simple_grep::version();
 ///
-/// assert_eq!(vec!["safe, fast, productive."], search(query, contents));
+/// assert!(version.starts_with(&format!("simple_grep {}\n", env!("CARGO_PKG_VERSION"))));
+/// assert!(version.contains("+gzip") || version.contains("-gzip"));
 /// ```
-pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    contents
-        .lines()
-        .filter(|line| line.contains(query))
-        .collect()
+pub fn version() -> String {
+    let features: Vec<String> = FEATURES
+        .iter()
+        .map(|&(name, enabled)| format!("{}{name}", if enabled { '+' } else { '-' }))
+        .collect();
+
+    format!(
+        "simple_grep {}\nfeatures: {}",
+        env!("CARGO_PKG_VERSION"),
+        features.join(" ")
+    )
 }
 
-/// Performs a **case-insensitive** search of `query` in `contents`.
+/// Searches `reader` line by line, writing each match to `out` and flushing
+/// it as soon as it is found.
 ///
-/// # Arguments
+/// Unlike [`run`], the input is never read as a whole, so this is suitable
+/// for input that keeps growing, such as the output of `tail -f`. This is
+/// what `--line-buffered` uses.
 ///
-/// * `query` - The search string (case-insensitive).
-/// * `contents` - The contents of the file as a string slice.
-///
-/// # Returns
+/// # Errors
 ///
-/// A vector of lines that contain the query, ignoring case.
+/// Returns an error if reading from `reader` or writing to `out` fails, or
+/// if the query is an invalid regular expression.
 ///
 /// # Examples
 ///
 /// ```
-/// use simple_grep::search_case_insensitive;
+/// use simple_grep::{Config, search_reader};
 ///
-/// let query = "rUsT";
-/// let contents = "\
-/// Rust:
-/// safe, fast, productive.
-/// Pick three.
-/// Trust me.";
+/// let config = Config {
+///     query: String::from("fast"),
+///     line_number: true,
+///     ..Config::default()
+/// };
+/// let mut out = Vec::new();
 ///
-/// assert_eq!(vec!["Rust:", "Trust me."], search_case_insensitive(query, contents));
+/// search_reader(&config, "Rust:\nsafe, fast, productive.".as_bytes(), &mut out)?;
+///
+/// assert_eq!(out, b"2:safe, fast, productive.\n");
+/// # Ok::<(), std::io::Error>(())
 /// ```
-pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase();
-    let mut results = Vec::new();
+pub fn search_reader(
+    config: &Config,
+    mut reader: impl BufRead,
+    out: &mut impl Write,
+) -> io::Result<()> {
+    let query =
+        Query::new(config).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+    let mut searcher = FileSearcher::new(config, &query, "(standard input)", false);
+    searcher.max_selected = config.max_total;
+    stream(&mut searcher, &mut reader, 0, 0, out)
+}
 
-    for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
-            results.push(line);
+/// Searches a single file (standard input for `-`) and writes its matches to
+/// `out`, or offers them to `sample`, returning its line counts. The search
+/// stops after `max_selected` selected lines. Lines are numbered from
+/// `first_line + 1`.
+#[allow(clippy::too_many_arguments)]
+fn search_file(
+    config: &Config,
+    query: &Query,
+    path: &Path,
+    show_filename: bool,
+    sample: &mut Option<Reservoir<Vec<u8>>>,
+    max_selected: Option<usize>,
+    first_line: usize,
+    out: &mut dyn Write,
+) -> Result<Stats, Box<dyn Error>> {
+    // Without `--include-partial`, seek to one byte before `--seek-bytes`,
+    // to find out whether the offset is in the middle of a line.
+    let partial = config.seek_bytes > 0 && !config.include_partial;
+    let seek = config.seek_bytes - u64::from(partial);
+
+    let mut reader = match &config.pre {
+        Some(command) => match input::preprocess(command, path) {
+            Ok(mut reader) => {
+                input::skip(&mut reader, seek)?;
+                reader
+            }
+            Err(err) if config.fail_fast => return Err(err.into()),
+            Err(err) => {
+                if !config.quiet_errors {
+                    eprintln!("simple_grep: warning: {}: {err}", path.display());
+                }
+
+                return Ok(Stats {
+                    failed: 1,
+                    ..Stats::default()
+                });
+            }
+        },
+        None => input::open_at(
+            path,
+            config.buffer_capacity(),
+            seek,
+            config.decompress_stdin,
+        )?,
+    };
+
+    // The offset in the file of the first line that is searched.
+    let mut start = usize::try_from(seek)?;
+
+    if partial {
+        start += input::skip_line(&mut reader)?;
+    }
+
+    let name = if input::is_stdin(path) {
+        String::from("(standard input)")
+    } else {
+        path.display().to_string()
+    };
+    let mut searcher = FileSearcher::new(config, query, &name, show_filename);
+    searcher.sample = sample.as_mut();
+    searcher.max_selected = max_selected;
+
+    if let Some(needle) = &config.hex {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        searcher.stats.bytes = bytes.len();
+
+        for offset in binary::find_all(&bytes, needle) {
+            if searcher.stopped() {
+                break;
+            }
+
+            searcher.stats.record(true);
+
+            if !config.count {
+                let snippet = binary::snippet(&bytes, offset, needle.len());
+                searcher
+                    .printer
+                    .write_hex_match(out, start + offset, &snippet)?;
+            }
         }
+
+        searcher.finish(out)?;
+        return Ok(searcher.stats);
     }
 
-    results
+    if config.find_non_ascii {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        searcher.stats.bytes = bytes.len();
+
+        for (index, line) in bytes.split_inclusive(|&byte| byte == b'\n').enumerate() {
+            if searcher.stopped() {
+                break;
+            }
+
+            let non_ascii = binary::non_ascii(line);
+            searcher.stats.record(!non_ascii.is_empty());
+
+            if !non_ascii.is_empty() && !config.count {
+                searcher
+                    .printer
+                    .write_non_ascii(out, first_line + index + 1, &non_ascii)?;
+            }
+        }
+
+        searcher.finish(out)?;
+        return Ok(searcher.stats);
+    }
+
+    if config.line_buffered
+        && !config.bottom_up
+        && !config.group_count
+        && !config.print_whole_file
+        && !config.file_all
+        && !config.slurp
+        && !config.multiline
+        && !config.whole_file_match
+    {
+        stream(&mut searcher, &mut reader, first_line, start, out)?;
+        return Ok(searcher.stats);
+    }
+
+    let mut contents = if config.strict_utf8 {
+        input::read_strict_utf8(&mut reader, start)?
+    } else {
+        let mut contents = String::new();
+        reader.read_to_string(&mut contents)?;
+        contents
+    };
+    searcher.stats.bytes = contents.len();
+
+    if let Some(form) = config.normalize {
+        contents = form.apply(&contents).into_owned();
+    }
+
+    // The length of the byte order mark removed with `--strip-bom`.
+    #[cfg(feature = "regex")]
+    let bom = if config.strip_bom && start == 0 && contents.starts_with(search::BOM) {
+        search::BOM.len_utf8()
+    } else {
+        0
+    };
+
+    let file_lines = || lines_from(config, &contents, first_line, start);
+
+    #[cfg(feature = "regex")]
+    if config.whole_file_match {
+        let matched = pattern::matches_whole_file(config, &contents[bom..])?;
+        searcher.stats.record(matched);
+        searcher.finish(out)?;
+        return Ok(searcher.stats);
+    }
+
+    #[cfg(feature = "regex")]
+    if config.multiline {
+        // The number of the line at `counted`, found by counting the line
+        // breaks since the previous match.
+        let (mut number, mut counted) = (first_line + 1, 0);
+        let body = &contents[bom..];
+
+        for (match_start, end) in pattern::multiline_spans(config, body)? {
+            if searcher.stopped() {
+                break;
+            }
+
+            searcher.stats.record(true);
+            number += body[counted..match_start].matches('\n').count();
+            counted = match_start;
+
+            if !config.count {
+                let text = &body[match_start..end];
+                searcher.printer.write_multiline_match(
+                    out,
+                    number,
+                    start + bom + match_start,
+                    text,
+                )?;
+            }
+        }
+
+        searcher.finish(out)?;
+        return Ok(searcher.stats);
+    }
+
+    if config.slurp {
+        for span in query.spans(&contents) {
+            if searcher.stopped() {
+                break;
+            }
+
+            searcher.stats.record(true);
+
+            if !config.count {
+                searcher.printer.write_snippet(out, &contents, span)?;
+            }
+        }
+
+        searcher.finish(out)?;
+        return Ok(searcher.stats);
+    }
+
+    if config.group_count {
+        let mut matches = Vec::new();
+
+        for line in file_lines() {
+            if searcher.stopped() {
+                break;
+            }
+
+            let selected = searcher.selects(line.text);
+            searcher.stats.record(selected);
+
+            if selected {
+                searcher.stats.selected_bytes += line.text.len();
+                searcher.record_patterns(line.text);
+                matches.push(line.text);
+            }
+        }
+
+        for (count, line) in group_count(matches) {
+            searcher.printer.write_group(out, count, line)?;
+        }
+
+        searcher.finish(out)?;
+        return Ok(searcher.stats);
+    }
+
+    if config.file_all {
+        let mut probe = FileSearcher::new(config, query, &name, show_filename);
+        let mut found = vec![false; config.queries().len()];
+
+        for line in file_lines() {
+            if probe.selects(line.text) {
+                for index in query.matching(line.text) {
+                    found[index] = true;
+                }
+            }
+
+            if found.iter().all(|&found| found) {
+                break;
+            }
+        }
+
+        if !found.iter().all(|&found| found) {
+            probe.stats.bytes = contents.len();
+            return Ok(probe.stats);
+        }
+    }
+
+    if config.print_whole_file {
+        let mut probe = FileSearcher::new(config, query, &name, show_filename);
+
+        if !file_lines().any(|line| probe.selects(line.text)) {
+            probe.stats.bytes = contents.len();
+            return Ok(probe.stats);
+        }
+
+        searcher.passthru = true;
+    }
+
+    let threads = config.threads.unwrap_or(1);
+    let chunks = threads.min(contents.len() / MIN_CHUNK_SIZE);
+
+    if config.parallel_within_file && chunks > 1 && searcher.lines_independent() {
+        search_chunks(&mut searcher, &contents, start, chunks, show_filename, out)?;
+    } else if config.bottom_up {
+        let lines: Vec<_> = file_lines().collect();
+
+        for line in lines.iter().rev() {
+            if searcher.stopped() {
+                break;
+            }
+
+            searcher.line(line, out)?;
+        }
+    } else {
+        for line in file_lines() {
+            if searcher.stopped() {
+                break;
+            }
+
+            searcher.line(&line, out)?;
+        }
+    }
+
+    searcher.finish(out)?;
+    Ok(searcher.stats)
+}
+
+/// Splits `contents`, which starts after line `first` and at byte `offset`
+/// of the file, into its lines, removing the byte order mark of the first
+/// line of the file with `--strip-bom`.
+fn lines_from<'a>(
+    config: &Config,
+    contents: &'a str,
+    first: usize,
+    offset: usize,
+) -> impl Iterator<Item = Line<'a>> {
+    let strip = config.strip_bom;
+
+    lines(contents).map(move |line| {
+        let line = Line {
+            number: first + line.number,
+            offset: offset + line.offset,
+            ..line
+        };
+
+        if strip { strip_bom(line) } else { line }
+    })
+}
+
+/// The smallest part of a file searched on its own thread with
+/// `--parallel-within-file`, so that small files aren't split up for
+/// nothing.
+const MIN_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Searches `contents`, which starts at byte `offset` of the file, split
+/// into `chunks` parts of whole lines that are each searched on their own
+/// thread, for `--parallel-within-file`.
+///
+/// Each part is searched into a buffer with line numbers counted from the
+/// lines before it, so the buffers written to `out` in order are identical to
+/// the output of a single-threaded search. The statistics of the parts are
+/// added to those of `searcher`, which must handle each line on its own, see
+/// [`FileSearcher::lines_independent`].
+fn search_chunks(
+    searcher: &mut FileSearcher,
+    contents: &str,
+    offset: usize,
+    chunks: usize,
+    show_filename: bool,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let (config, query, path) = (searcher.config, searcher.query, searcher.path);
+
+    thread::scope(|scope| {
+        // The number of lines before the next part.
+        let mut first = 0;
+        let mut handles = Vec::new();
+
+        for range in chunk_ranges(contents, chunks) {
+            let chunk = &contents[range.clone()];
+            let lines = lines_from(config, chunk, first, offset + range.start);
+            first += chunk.bytes().filter(|&byte| byte == b'\n').count();
+
+            handles.push(scope.spawn(move || {
+                let mut searcher = FileSearcher::new(config, query, path, show_filename);
+                let mut buffer = Vec::new();
+
+                for line in lines {
+                    if searcher.stopped() {
+                        break;
+                    }
+
+                    searcher.line(&line, &mut buffer)?;
+                }
+
+                Ok::<_, io::Error>((buffer, searcher.stats))
+            }));
+        }
+
+        for handle in handles {
+            let (buffer, stats) = handle
+                .join()
+                .unwrap_or_else(|panic| std::panic::resume_unwind(panic))?;
+            out.write_all(&buffer)?;
+            searcher.stats += stats;
+        }
+
+        Ok(())
+    })
+}
+
+/// Splits `contents` into at most `count` byte ranges of about the same
+/// length, each ending after a line break or at the end of `contents`.
+fn chunk_ranges(contents: &str, count: usize) -> Vec<Range<usize>> {
+    let bytes = contents.as_bytes();
+    let mut ranges = Vec::with_capacity(count);
+    let mut start = 0;
+
+    for index in 1..count {
+        let target = (bytes.len() * index / count).max(start);
+        let end = bytes[target..]
+            .iter()
+            .position(|&byte| byte == b'\n')
+            .map_or(bytes.len(), |position| target + position + 1);
+
+        if end > start {
+            ranges.push(start..end);
+            start = end;
+        }
+    }
+
+    if start < bytes.len() {
+        ranges.push(start..bytes.len());
+    }
+
+    ranges
+}
+
+/// Reads `reader`, which starts after line `number` and at byte `offset` of
+/// the file, one line at a time, flushing the output after each line that
+/// was written.
+fn stream(
+    searcher: &mut FileSearcher,
+    reader: &mut dyn BufRead,
+    mut number: usize,
+    mut offset: usize,
+    out: &mut dyn Write,
+) -> io::Result<()> {
+    let mut buffer = String::new();
+
+    loop {
+        if searcher.stopped() {
+            return searcher.finish(out);
+        }
+
+        buffer.clear();
+        let len = if searcher.config.strict_utf8 {
+            input::read_strict_line(reader, &mut buffer, offset)?
+        } else {
+            reader.read_line(&mut buffer)?
+        };
+
+        if len == 0 {
+            return searcher.finish(out);
+        }
+
+        searcher.stats.bytes += len;
+
+        let text = normalize(searcher.config.normalize, &buffer);
+
+        number += 1;
+        let mut line = Line {
+            number,
+            offset,
+            text: trim_line_terminator(&text),
+            crlf: text.ends_with("\r\n"),
+        };
+
+        if searcher.config.strip_bom {
+            line = strip_bom(line);
+        }
+
+        if searcher.line(&line, out)? {
+            out.flush()?;
+        }
+
+        offset += len;
+    }
+}
+
+/// The state of the search of a single file, fed one line at a time.
+struct FileSearcher<'a> {
+    config: &'a Config,
+    query: &'a Query,
+    /// The name of the file, as shown to the user.
+    path: &'a str,
+    printer: Printer<'a>,
+    stats: Stats,
+    /// The last `before_context` lines that weren't printed.
+    before: VecDeque<OwnedLine>,
+    /// How many more lines to print as context after the last selected line.
+    after_remaining: usize,
+    /// The number of the last line printed.
+    last_printed: Option<usize>,
+    /// Where selected lines are rendered to instead of `out` with `--sample`.
+    sample: Option<&'a mut Reservoir<Vec<u8>>>,
+    /// Whether the next line is inside a `--from`/`--to` range.
+    in_range: bool,
+    /// Print the lines that aren't selected too (`--passthru`, or a file
+    /// that matches with `--print-whole-file`).
+    passthru: bool,
+    /// The numbers of the selected lines, for `--numbers-only` and `--gaps`.
+    numbers: Vec<usize>,
+    /// The selected lines, to be printed sorted once all lines have been
+    /// handled with `--sort-lines`.
+    sorted: Option<Vec<OwnedLine>>,
+    /// The longest selected line so far with `--longest`, with its length
+    /// in characters.
+    longest: Option<(usize, OwnedLine)>,
+    /// How many lines may be selected before the search stops, for
+    /// `--max-total`.
+    max_selected: Option<usize>,
+    /// The number of lines dropped so far with `--skip`.
+    skipped: usize,
+    /// The last `--json` match with context, written once its `after`
+    /// context lines are known.
+    pending: Option<JsonMatch>,
+    /// The selected lines matching each query, rendered, to be appended to
+    /// the files of `--split-by-pattern` once all lines have been handled.
+    by_pattern: Option<Vec<Vec<u8>>>,
+}
+
+/// A `--json` match with the context lines printed around it.
+struct JsonMatch {
+    line: OwnedLine,
+    before: Vec<OwnedLine>,
+    after: Vec<OwnedLine>,
+}
+
+impl<'a> FileSearcher<'a> {
+    fn new(
+        config: &'a Config,
+        query: &'a Query,
+        path: &'a str,
+        show_filename: bool,
+    ) -> FileSearcher<'a> {
+        FileSearcher {
+            config,
+            query,
+            path,
+            printer: Printer::new(config, query, path, show_filename),
+            stats: Stats {
+                pattern_matches: vec![0; config.queries().len()],
+                ..Stats::default()
+            },
+            before: VecDeque::new(),
+            after_remaining: 0,
+            last_printed: None,
+            sample: None,
+            in_range: query.starts_in_range(),
+            passthru: config.passthru,
+            numbers: Vec::new(),
+            sorted: config.sort_lines.then(Vec::new),
+            longest: None,
+            max_selected: None,
+            skipped: 0,
+            pending: None,
+            by_pattern: config
+                .split_by_pattern
+                .as_ref()
+                .map(|_| vec![Vec::new(); config.queries().len()]),
+        }
+    }
+
+    /// Handles the next line of the file. Returns whether anything was written.
+    ///
+    /// Context is anchored on selected lines, so with `-v` the lines around
+    /// non-matching lines are printed.
+    fn line(&mut self, line: &Line, out: &mut dyn Write) -> io::Result<bool> {
+        let start = (self.config.regex && self.config.regex_timeout.is_some()).then(Instant::now);
+        let matched = self.selects(line.text);
+        // A line that timed out is skipped, even with `-v`.
+        let selected = !self.timed_out(start, line.number) && matched;
+        self.stats.record(selected);
+
+        if selected {
+            self.stats.selected_bytes += line.text.len();
+            self.record_patterns(line.text);
+        }
+
+        if self.config.first_only {
+            if selected {
+                self.printer.write_first_match(out, line)?;
+            }
+
+            return Ok(selected);
+        }
+
+        if self.config.length_histogram || self.config.percentile.is_some() {
+            if selected {
+                let length = line.text.chars().count();
+                *self.stats.lengths.entry(length).or_insert(0) += 1;
+            }
+
+            return Ok(false);
+        }
+
+        if self.config.numbers_only || self.config.gaps {
+            if selected {
+                self.numbers.push(line.number);
+            }
+
+            return Ok(false);
+        }
+
+        if self.config.longest {
+            if selected {
+                let length = line.text.chars().count();
+
+                // Ties go to the first of the longest lines.
+                if self
+                    .longest
+                    .as_ref()
+                    .is_none_or(|(longest, _)| length > *longest)
+                {
+                    self.longest = Some((length, line.to_owned_line()));
+                }
+            }
+
+            return Ok(false);
+        }
+
+        if self.config.ratio
+            || self.config.density
+            || self.config.summary
+            || self.config.count
+            || self.config.files_with_counts
+            || self.config.count_by_pattern
+            || self.config.pattern_file_coverage
+            || self.config.cooccurrence
+        {
+            return Ok(false);
+        }
+
+        if let Some(by_pattern) = &mut self.by_pattern {
+            if selected {
+                for index in self.query.matching(line.text) {
+                    self.printer.write_match(&mut by_pattern[index], line)?;
+                }
+            }
+
+            return Ok(false);
+        }
+
+        if let Some(sample) = &mut self.sample {
+            if selected {
+                let printer = &self.printer;
+                sample.offer_with(|| {
+                    let mut rendered = Vec::new();
+                    // Writing to a `Vec` can't fail.
+                    let _ = printer.write_match(&mut rendered, line);
+                    rendered
+                });
+            }
+
+            return Ok(false);
+        }
+
+        if let Some(sorted) = &mut self.sorted {
+            if selected {
+                sorted.push(line.to_owned_line());
+            }
+
+            return Ok(false);
+        }
+
+        if selected && self.json_context() {
+            // Context lines belong to a single match, so those printed after
+            // the last match aren't printed before this one as well.
+            self.write_pending(out)?;
+            self.pending = Some(JsonMatch {
+                line: line.to_owned_line(),
+                before: self.before.drain(..).collect(),
+                after: Vec::new(),
+            });
+            self.after_remaining = self.config.after_context;
+        } else if selected {
+            let first = self
+                .before
+                .front()
+                .map_or(line.number, |context| context.number);
+            self.write_separator(first, out)?;
+
+            for context in self.before.drain(..) {
+                self.printer.write_context(out, &context.as_line())?;
+            }
+
+            self.printer.write_match(out, line)?;
+            self.after_remaining = self.config.after_context;
+        } else if let Some(pending) = self.pending.as_mut().filter(|_| self.after_remaining > 0) {
+            pending.after.push(line.to_owned_line());
+            self.after_remaining -= 1;
+        } else if self.passthru {
+            self.write_pending(out)?;
+            self.printer.write_context(out, line)?;
+        } else if self.after_remaining > 0 {
+            self.printer.write_context(out, line)?;
+            self.after_remaining -= 1;
+        } else {
+            let written = self.write_pending(out)?;
+
+            if self.config.before_context > 0 {
+                if self.before.len() == self.config.before_context {
+                    self.before.pop_front();
+                }
+                self.before.push_back(line.to_owned_line());
+            }
+
+            return Ok(written);
+        }
+
+        self.last_printed = Some(line.number);
+        Ok(true)
+    }
+
+    /// Returns `true` if matches are written as `--json` objects with their
+    /// context lines, with `-A`, `-B` or `-C`.
+    fn json_context(&self) -> bool {
+        self.config.json
+            && self.config.format.is_none()
+            && (self.config.before_context > 0 || self.config.after_context > 0)
+    }
+
+    /// Writes the `--json` match waiting for its `after` context lines, if
+    /// any. Returns whether there was one.
+    fn write_pending(&mut self, out: &mut dyn Write) -> io::Result<bool> {
+        let Some(pending) = self.pending.take() else {
+            return Ok(false);
+        };
+
+        self.printer.write_json_match(
+            out,
+            &pending.line.as_line(),
+            &pending.before,
+            &pending.after,
+        )?;
+        Ok(true)
+    }
+
+    /// Returns `true` if each line is handled the same no matter which lines
+    /// came before it, so that parts of the file can be searched on their
+    /// own with `--parallel-within-file`. Context, ranges, `--skip`,
+    /// `--density`, `--bottom-up` and the options that collect the lines of
+    /// the whole file rule that out.
+    fn lines_independent(&self) -> bool {
+        !self.query.has_range()
+            && self.config.skip == 0
+            && self.config.before_context == 0
+            && self.config.after_context == 0
+            && !self.passthru
+            && self.sample.is_none()
+            && self.max_selected.is_none()
+            && self.sorted.is_none()
+            && self.by_pattern.is_none()
+            && !self.config.first_only
+            && !self.config.numbers_only
+            && !self.config.gaps
+            && !self.config.longest
+            && !self.config.density
+            && !self.config.bottom_up
+    }
+
+    /// Returns `true` if matching line `number`, which started at `start`,
+    /// took longer than the `--regex-timeout`, warning on stderr that the
+    /// line is skipped.
+    fn timed_out(&self, start: Option<Instant>, number: usize) -> bool {
+        let (Some(start), Some(limit)) = (start, self.config.regex_timeout) else {
+            return false;
+        };
+
+        let timed_out = start.elapsed() > limit;
+        if timed_out {
+            eprintln!(
+                "simple_grep: warning: {}:{number}: matching took longer than {}ms, line skipped",
+                self.path,
+                limit.as_millis()
+            );
+        }
+
+        timed_out
+    }
+
+    /// Counts the selected line `text` for each query it matches, with
+    /// `--count-by-pattern`, `--report-unmatched` and
+    /// `--pattern-file-coverage`, and for each pair of them with
+    /// `--cooccurrence`.
+    fn record_patterns(&mut self, text: &str) {
+        if self.config.count_by_pattern
+            || self.config.report_unmatched
+            || self.config.pattern_file_coverage
+            || self.config.cooccurrence
+        {
+            let matching: Vec<usize> = self.query.matching(text).collect();
+
+            for &index in &matching {
+                self.stats.pattern_matches[index] += 1;
+            }
+
+            if self.config.cooccurrence {
+                for (position, &first) in matching.iter().enumerate() {
+                    for &second in &matching[position + 1..] {
+                        *self.stats.pattern_pairs.entry((first, second)).or_insert(0) += 1;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns `true` once the search should stop: when it is cancelled,
+    /// when `max_selected` lines have been selected, or after the first
+    /// selected line with `--first-only`.
+    fn stopped(&self) -> bool {
+        self.config.cancel.is_cancelled()
+            || self
+                .max_selected
+                .is_some_and(|max| self.stats.selected >= max)
+            || (self.config.first_only && self.stats.selected > 0)
+    }
+
+    /// Returns `true` if the next line, `text`, is selected. Lines outside of
+    /// the `--from`/`--to` ranges never are, nor the first `--skip` lines
+    /// that would be.
+    fn selects(&mut self, text: &str) -> bool {
+        if !self.in_range {
+            self.in_range = self.query.starts_range(text);
+        }
+
+        let in_range = self.in_range;
+
+        if in_range && self.query.ends_range(text) {
+            self.in_range = false;
+        }
+
+        if !in_range || !self.query.selects(text) {
+            return false;
+        }
+
+        if self.skipped < self.config.skip {
+            self.skipped += 1;
+            return false;
+        }
+
+        true
+    }
+
+    /// Writes the `--` separator if context is enabled and the group of lines
+    /// starting at line `first` doesn't follow the last printed line, from
+    /// above or, with `--bottom-up`, from below.
+    fn write_separator(&self, first: usize, out: &mut dyn Write) -> io::Result<()> {
+        let context = self.config.before_context > 0 || self.config.after_context > 0;
+
+        match self.last_printed {
+            Some(last) if context && first.abs_diff(last) > 1 => self.printer.write_separator(out),
+            _ => Ok(()),
+        }
+    }
+
+    /// Writes the last `--json` match, the `--sort-lines` lines, the
+    /// `--split-by-pattern` lines and the per-file summaries once all lines
+    /// have been handled.
+    fn finish(&mut self, out: &mut dyn Write) -> io::Result<()> {
+        self.write_pending(out)?;
+
+        if let (Some(dir), Some(by_pattern)) =
+            (&self.config.split_by_pattern, self.by_pattern.take())
+        {
+            for (index, lines) in by_pattern.iter().enumerate() {
+                if !lines.is_empty() {
+                    create_output(&pattern_output_path(Path::new(dir), index), true)?
+                        .write_all(lines)?;
+                }
+            }
+        }
+
+        if let Some(mut sorted) = self.sorted.take() {
+            if self.config.reverse {
+                sorted.sort_by(|a, b| b.text.cmp(&a.text));
+            } else {
+                sorted.sort_by(|a, b| a.text.cmp(&b.text));
+            }
+
+            for line in &sorted {
+                self.printer.write_match(out, &line.as_line())?;
+            }
+        }
+
+        if let Some((_, line)) = self.longest.take() {
+            self.printer.write_longest(out, &line.as_line())?;
+        }
+
+        if self.config.count {
+            self.printer.write_count(out, &self.stats)?;
+        }
+
+        if self.config.numbers_only && !self.numbers.is_empty() {
+            self.printer.write_numbers(out, &self.numbers)?;
+        }
+
+        if self.config.gaps && self.numbers.len() > 1 {
+            self.printer.write_gaps(out, &self.numbers)?;
+        }
+
+        if self.config.count_by_pattern {
+            self.printer
+                .write_pattern_counts(out, &self.stats.pattern_matches)?;
+        }
+
+        if self.config.files_with_counts && self.stats.selected > 0 {
+            self.printer.write_file_with_count(out, &self.stats)?;
+        }
+
+        if self.config.ratio {
+            self.printer.write_ratio(out, &self.stats)?;
+        }
+
+        if self.config.density {
+            self.printer.write_density(out, &self.stats)?;
+        }
+
+        if let Some(percentile) = self.config.percentile {
+            self.printer
+                .write_percentile(out, &self.stats, percentile)?;
+        }
+
+        if self.config.summary {
+            self.printer.write_summary(out, &self.stats)?;
+        }
+
+        Ok(())
+    }
 }
@@ -1,16 +1,61 @@
 use std::env;
 use std::process;
 
-use simple_grep::Config;
+use simple_grep::{Config, SystemClipboard};
 
 fn main() {
-    let config = Config::build(env::args()).unwrap_or_else(|err| {
+    let mut config = Config::build(env::args()).unwrap_or_else(|err| {
         eprintln!("Problem parsing arguments: {err}");
         process::exit(1);
     });
 
-    if let Err(e) = simple_grep::run(config) {
-        eprintln!("Application error: {e}");
-        process::exit(1);
-    };
+    if config.help {
+        print!("{}", simple_grep::help());
+        return;
+    }
+
+    if config.list_options {
+        print!("{}", simple_grep::list_options());
+        return;
+    }
+
+    if config.version {
+        println!("{}", simple_grep::version());
+        return;
+    }
+
+    if config.pattern_clipboard {
+        config.query = simple_grep::clipboard_query(&mut SystemClipboard).unwrap_or_else(|err| {
+            eprintln!("simple_grep: {err}");
+            process::exit(1);
+        });
+    }
+
+    #[cfg(feature = "ctrlc")]
+    {
+        let cancel = config.cancel.clone();
+        if let Err(err) = ctrlc::set_handler(move || cancel.cancel()) {
+            eprintln!("simple_grep: can't handle Ctrl-C: {err}");
+        }
+    }
+
+    let (whole_file_match, quiet_errors) = (config.whole_file_match, config.quiet_errors);
+    let json = config.json;
+
+    // Skipped files take precedence over not matching, like in `grep`, and
+    // a search stopped with Ctrl-C over both, with the status of SIGINT.
+    match simple_grep::run(config) {
+        Ok(summary) if summary.cancelled => process::exit(130),
+        Ok(summary) if quiet_errors && summary.failed > 0 => process::exit(2),
+        Ok(summary) if whole_file_match && summary.selected == 0 => process::exit(1),
+        Ok(_) => {}
+        Err(e) if json => {
+            eprintln!("{}", simple_grep::error_json(e.as_ref()));
+            process::exit(1);
+        }
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            process::exit(1);
+        }
+    }
 }
@@ -0,0 +1,152 @@
+//! Matching a query against a single line.
+
+/// Finds the occurrences of a query in a line.
+///
+/// Implementations decide what counts as an occurrence: the literal query,
+/// or a fuzzy approximation of it such as [`FuzzyMatcher`](crate::FuzzyMatcher).
+pub trait Matcher {
+    /// Returns the byte ranges of the non-overlapping matches in `line`,
+    /// from left to right.
+    fn find_spans(&self, line: &str) -> Vec<(usize, usize)>;
+
+    /// Returns `true` if `line` contains a match.
+    ///
+    /// The default implementation looks for spans; implementations should
+    /// override it when there's a cheaper test.
+    fn is_match(&self, line: &str) -> bool {
+        !self.find_spans(line).is_empty()
+    }
+}
+
+/// Matches the query literally, as a substring.
+pub(crate) struct LiteralMatcher {
+    /// The query itself, lowercased for [`Case::Insensitive`].
+    text: String,
+    case: Case,
+}
+
+impl LiteralMatcher {
+    pub(crate) fn new(query: &str, case: Case) -> LiteralMatcher {
+        let text = match case {
+            Case::Insensitive => query.to_lowercase(),
+            _ => query.to_string(),
+        };
+
+        LiteralMatcher { text, case }
+    }
+}
+
+impl Matcher for LiteralMatcher {
+    fn find_spans(&self, line: &str) -> Vec<(usize, usize)> {
+        match_spans(&self.text, line, self.case)
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        line_matches(&self.text, line, self.case)
+    }
+}
+
+/// How letter case is treated when comparing a query with a line.
+#[derive(Clone, Copy)]
+pub(crate) enum Case {
+    Sensitive,
+    /// Full Unicode case folding via `to_lowercase`.
+    Insensitive,
+    /// ASCII-only case folding, without allocating.
+    AsciiInsensitive,
+}
+
+/// Returns `true` if `line` contains `query`.
+///
+/// With [`Case::Insensitive`], `query` must already be lowercase.
+pub(crate) fn line_matches(query: &str, line: &str, case: Case) -> bool {
+    match case {
+        Case::Sensitive => line.contains(query),
+        Case::Insensitive => line.to_lowercase().contains(query),
+        Case::AsciiInsensitive => contains_ignore_ascii_case(line, query),
+    }
+}
+
+/// Returns the byte ranges of the non-overlapping occurrences of `query`
+/// in `line`, from left to right.
+///
+/// With [`Case::Insensitive`], `query` must already be lowercase. Empty
+/// occurrences are never reported.
+fn match_spans(query: &str, line: &str, case: Case) -> Vec<(usize, usize)> {
+    if query.is_empty() {
+        return Vec::new();
+    }
+
+    match case {
+        Case::Sensitive => line
+            .match_indices(query)
+            .map(|(start, found)| (start, start + found.len()))
+            .collect(),
+        Case::AsciiInsensitive => {
+            let (line, query) = (line.as_bytes(), query.as_bytes());
+            let mut spans = Vec::new();
+            let mut start = 0;
+
+            while start + query.len() <= line.len() {
+                if line[start..start + query.len()].eq_ignore_ascii_case(query) {
+                    spans.push((start, start + query.len()));
+                    start += query.len();
+                } else {
+                    start += 1;
+                }
+            }
+
+            spans
+        }
+        Case::Insensitive => {
+            // Lowercasing can change the length of the text, so the spans are
+            // found by lowercasing the line one character at a time from each
+            // possible starting point.
+            let mut spans = Vec::new();
+            let mut start = 0;
+
+            while start < line.len() {
+                match lowercase_prefix_len(&line[start..], query) {
+                    Some(len) => {
+                        spans.push((start, start + len));
+                        start += len;
+                    }
+                    None => start += line[start..].chars().next().map_or(1, char::len_utf8),
+                }
+            }
+
+            spans
+        }
+    }
+}
+
+/// Returns the byte length of the shortest prefix of `text` whose lowercase
+/// form equals `query`, if there is one.
+fn lowercase_prefix_len(text: &str, query: &str) -> Option<usize> {
+    let mut lowered = String::new();
+
+    for (index, c) in text.char_indices() {
+        lowered.extend(c.to_lowercase());
+
+        if lowered.len() >= query.len() {
+            return (lowered == query).then_some(index + c.len_utf8());
+        }
+
+        if !query.starts_with(&lowered) {
+            return None;
+        }
+    }
+
+    None
+}
+
+/// Returns `true` if `haystack` contains `needle`, ignoring ASCII case.
+fn contains_ignore_ascii_case(haystack: &str, needle: &str) -> bool {
+    let needle = needle.as_bytes();
+
+    needle.is_empty()
+        || haystack
+            .as_bytes()
+            .windows(needle.len())
+            .any(|window| window.eq_ignore_ascii_case(needle))
+}
@@ -0,0 +1,26 @@
+//! Dropping short matches, for `--min-match-len`.
+
+use crate::Matcher;
+
+/// Keeps the matches of another matcher that are at least `min` characters
+/// long, so that a line only matches if one of them is.
+pub(crate) struct MinLengthMatcher {
+    inner: Box<dyn Matcher + Send + Sync>,
+    min: usize,
+}
+
+impl MinLengthMatcher {
+    pub(crate) fn new(inner: Box<dyn Matcher + Send + Sync>, min: usize) -> MinLengthMatcher {
+        MinLengthMatcher { inner, min }
+    }
+}
+
+impl Matcher for MinLengthMatcher {
+    fn find_spans(&self, line: &str) -> Vec<(usize, usize)> {
+        self.inner
+            .find_spans(line)
+            .into_iter()
+            .filter(|&(start, end)| line[start..end].chars().count() >= self.min)
+            .collect()
+    }
+}
@@ -0,0 +1,52 @@
+//! Searching for many literal patterns at once.
+
+use aho_corasick::{AhoCorasick, AhoCorasickKind};
+
+use crate::Config;
+
+/// The number of `-e` patterns from which lines are scanned for all of them
+/// at once, rather than for one pattern after the other.
+const MIN_PATTERNS: usize = 8;
+
+/// Finds out whether a line contains any of several literal patterns,
+/// scanning it once for all of them with the Aho-Corasick algorithm.
+///
+/// This is the same as checking each pattern in turn, but doesn't slow down
+/// with the number of patterns.
+pub(crate) struct MultiLiteral {
+    automaton: AhoCorasick,
+}
+
+impl MultiLiteral {
+    /// Builds the automaton for the `patterns` of `config`, which are
+    /// already normalized, if there are enough of them and they're matched
+    /// literally, case-sensitively or with ASCII case folding. Returns `None`
+    /// otherwise, to check each pattern in turn.
+    pub(crate) fn new(config: &Config, patterns: &[String]) -> Option<MultiLiteral> {
+        let literal = !config.regex
+            && config.fuzzy.is_none()
+            && !config.squeeze_whitespace
+            && !config.case_variants
+            && !config.word_regexp
+            && !config.word_start
+            && config.min_match_len == 0;
+
+        if !literal || (config.ignore_case && !config.ascii_case) || patterns.len() < MIN_PATTERNS {
+            return None;
+        }
+
+        let automaton = AhoCorasick::builder()
+            .ascii_case_insensitive(config.ignore_case)
+            .kind(Some(AhoCorasickKind::DFA))
+            .build(patterns)
+            // Too many patterns for a DFA: fall back to checking each one.
+            .ok()?;
+
+        Some(MultiLiteral { automaton })
+    }
+
+    /// Returns `true` if `line` contains any of the patterns.
+    pub(crate) fn is_match(&self, line: &str) -> bool {
+        self.automaton.is_match(line)
+    }
+}
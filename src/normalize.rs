@@ -0,0 +1,64 @@
+//! Unicode normalization of the query and the input for `--normalize`.
+
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// A Unicode normalization form, set with `--normalize`.
+///
+/// The same text can be written with precomposed characters, such as `é`
+/// (U+00E9), or with a base character followed by combining marks, such as
+/// `e` and U+0301. Normalizing both the query and the lines to the same form
+/// makes them match either way.
+///
+/// Normalization requires the `normalize` feature.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Normalization {
+    /// Canonical composition: combining marks are merged into precomposed
+    /// characters where possible.
+    Nfc,
+    /// Canonical decomposition: precomposed characters are split into a base
+    /// character and combining marks.
+    Nfd,
+}
+
+impl Normalization {
+    /// Returns `text` in this normalization form.
+    #[cfg(feature = "normalize")]
+    pub(crate) fn apply(self, text: &str) -> Cow<'_, str> {
+        use unicode_normalization::UnicodeNormalization;
+
+        match self {
+            Normalization::Nfc => Cow::Owned(text.nfc().collect()),
+            Normalization::Nfd => Cow::Owned(text.nfd().collect()),
+        }
+    }
+
+    /// Without the `normalize` feature, [`Query`](crate::search::Query)
+    /// rejects normalization before any text is normalized.
+    #[cfg(not(feature = "normalize"))]
+    pub(crate) fn apply(self, text: &str) -> Cow<'_, str> {
+        Cow::Borrowed(text)
+    }
+}
+
+impl FromStr for Normalization {
+    type Err = String;
+
+    fn from_str(form: &str) -> Result<Normalization, String> {
+        match form.to_ascii_uppercase().as_str() {
+            "NFC" => Ok(Normalization::Nfc),
+            "NFD" => Ok(Normalization::Nfd),
+            _ => Err(format!(
+                "Invalid normalization form '{form}': expected 'NFC' or 'NFD'"
+            )),
+        }
+    }
+}
+
+/// Returns `text` in the normalization form `form`, or unchanged without one.
+pub(crate) fn normalize(form: Option<Normalization>, text: &str) -> Cow<'_, str> {
+    match form {
+        Some(form) => form.apply(text),
+        None => Cow::Borrowed(text),
+    }
+}
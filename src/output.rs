@@ -0,0 +1,1088 @@
+//! Formatting of matches for output.
+
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs::File;
+use std::io::{self, IsTerminal, Write};
+use std::path::{Component, Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+
+use crate::Config;
+use crate::search::{Line, OwnedLine, Query};
+use crate::stats::Stats;
+
+/// Escape sequence starting a highlighted match (bold red).
+const COLOR_MATCH: &str = "\x1b[1;31m";
+
+/// Escape sequence starting text dimmed around the matches with
+/// `--dim-context` (faint).
+const COLOR_DIM: &str = "\x1b[2m";
+
+/// Escape sequence resetting all colors.
+pub(crate) const COLOR_RESET: &str = "\x1b[0m";
+
+/// Text written before a match with `--mark`.
+const MARK_OPEN: &str = ">>";
+
+/// Text written after a match with `--mark`.
+const MARK_CLOSE: &str = "<<";
+
+/// When to highlight matches with colors, set with `--color`.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum ColorChoice {
+    /// Never use colors.
+    #[default]
+    Never,
+    /// Always use colors, even when the output isn't a terminal.
+    Always,
+    /// Use colors only when stdout is a terminal.
+    Auto,
+}
+
+impl FromStr for ColorChoice {
+    type Err = String;
+
+    fn from_str(choice: &str) -> Result<ColorChoice, String> {
+        match choice {
+            "never" => Ok(ColorChoice::Never),
+            "always" => Ok(ColorChoice::Always),
+            "auto" => Ok(ColorChoice::Auto),
+            _ => Err(format!(
+                "Invalid color choice '{choice}': expected 'never', 'always' or 'auto'"
+            )),
+        }
+    }
+}
+
+/// Writes the matches of a single file in the format selected by a [`Config`].
+pub(crate) struct Printer<'a> {
+    config: &'a Config,
+    query: &'a Query,
+    /// The name of the file, as shown to the user.
+    path: &'a str,
+    /// Whether lines are prefixed with the file name.
+    show_filename: bool,
+    /// Whether matches are highlighted with colors.
+    color: bool,
+}
+
+impl<'a> Printer<'a> {
+    pub(crate) fn new(
+        config: &'a Config,
+        query: &'a Query,
+        path: &'a str,
+        show_filename: bool,
+    ) -> Printer<'a> {
+        Printer {
+            config,
+            query,
+            path,
+            show_filename,
+            color: use_color(config),
+        }
+    }
+
+    /// Writes a matching line, or its matches with `-o`.
+    pub(crate) fn write_match(&self, out: &mut dyn Write, line: &Line) -> io::Result<()> {
+        let spans = self.query.spans(line.text);
+
+        if self.config.format.is_some() || self.config.csv || self.config.json {
+            for text in self.record_texts(line, &spans) {
+                let record = Record {
+                    path: self.path,
+                    line_number: line.number,
+                    text: &text,
+                    count: spans.len(),
+                };
+                self.write_record(out, &record)?;
+            }
+
+            return Ok(());
+        }
+
+        let labels = match self.query.labels(line.text) {
+            Some(labels) => labels + " ",
+            None => String::new(),
+        };
+
+        if self.config.only_matching {
+            let line_offset = if self.config.offset_relative {
+                0
+            } else {
+                line.offset
+            };
+
+            for &span in self.occurrences(&spans) {
+                let (start, end) = span;
+                let prefix = self.timestamp() + &self.prefix(line.number, line_offset + start, ':');
+                let columns = self.columns(line.text, Some(span));
+                let chars = self.char_count(&line.text[start..end]);
+                let text = self.display(&line.text[start..end], &[(0, end - start)]);
+                writeln!(out, "{prefix}{columns}{labels}{chars}{text}")?;
+            }
+        } else {
+            let prefix = self.timestamp() + &self.prefix(line.number, line.offset, ':');
+            let columns = self.columns(line.text, spans.first().copied());
+            let chars = self.char_count(line.text);
+            let text = self.display(line.text, &spans);
+            write!(
+                out,
+                "{prefix}{columns}{labels}{chars}{text}{}",
+                self.line_end(line)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes a `--json` match with the context lines printed around it, as
+    /// `before` and `after` arrays of objects with `line_number` and `text`
+    /// fields.
+    pub(crate) fn write_json_match(
+        &self,
+        out: &mut dyn Write,
+        line: &Line,
+        before: &[OwnedLine],
+        after: &[OwnedLine],
+    ) -> io::Result<()> {
+        let spans = self.query.spans(line.text);
+        let context = format!(
+            ",\"before\":{},\"after\":{}",
+            json_context_lines(before),
+            json_context_lines(after)
+        );
+
+        for text in self.record_texts(line, &spans) {
+            writeln!(
+                out,
+                "{{\"path\":{},\"line_number\":{},\"text\":{}{context}}}",
+                json_string(self.path),
+                line.number,
+                json_string(&text)
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns the texts written as records for `line`: its matches with
+    /// `-o`, and the whole line otherwise.
+    fn record_texts(&self, line: &Line, spans: &[(usize, usize)]) -> Vec<String> {
+        if self.config.only_matching {
+            self.occurrences(spans)
+                .map(|&(start, end)| line.text[start..end].to_string())
+                .collect()
+        } else if self.config.csv || self.config.json {
+            vec![line.text.to_string()]
+        } else {
+            vec![self.display(line.text, &[])]
+        }
+    }
+
+    /// Writes a `--multiline` match, which may span several lines, prefixed
+    /// with the number of the line it starts on and its byte `offset`. With
+    /// `--flatten-matches`, its line breaks are written as `\n`.
+    #[cfg(feature = "regex")]
+    pub(crate) fn write_multiline_match(
+        &self,
+        out: &mut dyn Write,
+        number: usize,
+        offset: usize,
+        text: &str,
+    ) -> io::Result<()> {
+        let prefix = self.timestamp() + &self.prefix(number, offset, ':');
+        let text = if self.config.flatten_matches {
+            Cow::Owned(text.replace('\n', "\\n"))
+        } else {
+            Cow::Borrowed(text)
+        };
+        writeln!(out, "{prefix}{}", self.display(&text, &[(0, text.len())]))
+    }
+
+    /// Returns the spans reported individually with `-o`, at most
+    /// `--max-matches-per-line` of them.
+    fn occurrences<'s>(
+        &self,
+        spans: &'s [(usize, usize)],
+    ) -> impl Iterator<Item = &'s (usize, usize)> {
+        spans
+            .iter()
+            .take(self.config.max_matches_per_line.unwrap_or(usize::MAX))
+    }
+
+    /// Writes a match as a `--format` template, a `--json` object or a
+    /// `--csv` row.
+    fn write_record(&self, out: &mut dyn Write, record: &Record) -> io::Result<()> {
+        match &self.config.format {
+            Some(format) => writeln!(out, "{}", format.render(record)),
+            None if self.config.json => writeln!(
+                out,
+                "{{\"path\":{},\"line_number\":{},\"text\":{}}}",
+                json_string(record.path),
+                record.line_number,
+                json_string(record.text)
+            ),
+            None => writeln!(
+                out,
+                "{},{},{}",
+                csv_field(record.path),
+                record.line_number,
+                csv_field(record.text)
+            ),
+        }
+    }
+
+    /// Writes a line that isn't selected but is printed anyway, as context
+    /// or with `--passthru`. Its prefix uses `-` instead of `:`, like `grep`.
+    pub(crate) fn write_context(&self, out: &mut dyn Write, line: &Line) -> io::Result<()> {
+        let prefix = self.prefix(line.number, line.offset, '-');
+        let text = self.display(line.text, &[]);
+        write!(out, "{prefix}{text}{}", self.line_end(line))
+    }
+
+    /// Returns the line terminator written after `line`: `\r\n` if it ended
+    /// with one with `--respect-crlf`, and `\n` otherwise.
+    fn line_end(&self, line: &Line) -> &'static str {
+        if self.config.respect_crlf && line.crlf {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+
+    /// Writes the `--` line separating groups of context lines.
+    pub(crate) fn write_separator(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "--")
+    }
+
+    /// Writes a line of `--group-count` output.
+    pub(crate) fn write_group(
+        &self,
+        out: &mut dyn Write,
+        count: usize,
+        line: &str,
+    ) -> io::Result<()> {
+        let prefix = self.prefix_filename(':');
+        writeln!(out, "{prefix}{count:>4}  {line}")
+    }
+
+    /// Writes a `--hex` match at byte `offset`, with a hex dump of the bytes
+    /// around it.
+    pub(crate) fn write_hex_match(
+        &self,
+        out: &mut dyn Write,
+        offset: usize,
+        snippet: &str,
+    ) -> io::Result<()> {
+        let prefix = self.prefix_filename(':');
+        writeln!(out, "{prefix}{offset}: {snippet}")
+    }
+
+    /// Writes the non-ASCII bytes of line `number` for `--find-non-ascii`, as
+    /// their columns and values, like `2: 4:0xc3 5:0xa9`.
+    pub(crate) fn write_non_ascii(
+        &self,
+        out: &mut dyn Write,
+        number: usize,
+        bytes: &[(usize, u8)],
+    ) -> io::Result<()> {
+        let bytes: Vec<String> = bytes
+            .iter()
+            .map(|(column, byte)| format!("{column}:0x{byte:02x}"))
+            .collect();
+        let prefix = self.prefix_filename(':');
+        writeln!(out, "{prefix}{number}: {}", bytes.join(" "))
+    }
+
+    /// Writes the `-c` count of the file.
+    pub(crate) fn write_count(&self, out: &mut dyn Write, stats: &Stats) -> io::Result<()> {
+        let prefix = self.prefix_filename(':');
+        writeln!(out, "{prefix}{}", stats.selected)
+    }
+
+    /// Writes the `--first-only` position of `line`, the first selected line
+    /// of the file, as `path:lineno:col`. The column is the 1-based position
+    /// in characters of the first match, or 1 for a line without one (with
+    /// `-v`).
+    pub(crate) fn write_first_match(&self, out: &mut dyn Write, line: &Line) -> io::Result<()> {
+        let column = match self.query.spans(line.text).first() {
+            Some(&(start, _)) => line.text[..start].chars().count() + 1,
+            None => 1,
+        };
+        writeln!(out, "{}:{}:{column}", self.path, line.number)
+    }
+
+    /// Writes the `--slurp` match at `span` of the whole `contents` of the
+    /// file as a snippet with up to [`SNIPPET_CONTEXT`] characters around
+    /// it, always prefixed with the file name. Line breaks are written as
+    /// `\n`, so that every snippet takes a single line.
+    pub(crate) fn write_snippet(
+        &self,
+        out: &mut dyn Write,
+        contents: &str,
+        (start, end): (usize, usize),
+    ) -> io::Result<()> {
+        let from = contents[..start]
+            .char_indices()
+            .rev()
+            .nth(SNIPPET_CONTEXT - 1)
+            .map_or(0, |(index, _)| index);
+        let to = contents[end..]
+            .char_indices()
+            .nth(SNIPPET_CONTEXT)
+            .map_or(contents.len(), |(index, _)| end + index);
+
+        let flatten = |text: &str| text.replace('\n', "\\n");
+        let before = flatten(&contents[from..start]);
+        let matched = flatten(&contents[start..end]);
+        let snippet = before.clone() + &matched + &flatten(&contents[end..to]);
+        let span = (before.len(), before.len() + matched.len());
+
+        writeln!(out, "{}:{}", self.path, self.display(&snippet, &[span]))
+    }
+
+    /// Writes the `--longest` selected line of the file, always prefixed with
+    /// its line number.
+    pub(crate) fn write_longest(&self, out: &mut dyn Write, line: &Line) -> io::Result<()> {
+        let prefix = self.prefix_filename(':');
+        let text = self.display(line.text, &self.query.spans(line.text));
+        writeln!(out, "{prefix}{}:{text}", line.number)
+    }
+
+    /// Writes the `--numbers-only` line numbers of the file.
+    pub(crate) fn write_numbers(&self, out: &mut dyn Write, numbers: &[usize]) -> io::Result<()> {
+        let numbers: Vec<String> = numbers.iter().map(usize::to_string).collect();
+        let prefix = self.prefix_filename(':');
+        writeln!(out, "{prefix}{}", numbers.join(","))
+    }
+
+    /// Writes the `--gaps` between the successive selected lines of the
+    /// file, `numbers`, which always names the file.
+    pub(crate) fn write_gaps(&self, out: &mut dyn Write, numbers: &[usize]) -> io::Result<()> {
+        let gaps: Vec<usize> = numbers
+            .windows(2)
+            .map(|pair| pair[0].abs_diff(pair[1]))
+            .collect();
+        let max = gaps.iter().max().copied().unwrap_or(0);
+        let gaps: Vec<String> = gaps.iter().map(usize::to_string).collect();
+
+        writeln!(out, "{}: gaps {} (max {max})", self.path, gaps.join(","))
+    }
+
+    /// Writes the `--count-by-pattern` counts of the file, `counts[i]` being
+    /// that of the `i`-th query.
+    pub(crate) fn write_pattern_counts(
+        &self,
+        out: &mut dyn Write,
+        counts: &[usize],
+    ) -> io::Result<()> {
+        let counts: Vec<String> = self
+            .config
+            .queries()
+            .iter()
+            .zip(counts)
+            .map(|(query, count)| format!("{query}: {count}"))
+            .collect();
+        let prefix = self.prefix_filename(':');
+        writeln!(out, "{prefix}{}", counts.join(", "))
+    }
+
+    /// Writes the `--files-with-counts` line of the file, which always names
+    /// the file.
+    pub(crate) fn write_file_with_count(
+        &self,
+        out: &mut dyn Write,
+        stats: &Stats,
+    ) -> io::Result<()> {
+        writeln!(out, "{} ({})", self.path, stats.selected)
+    }
+
+    /// Writes the `--ratio` summary of the file, which always names the file.
+    pub(crate) fn write_ratio(&self, out: &mut dyn Write, stats: &Stats) -> io::Result<()> {
+        writeln!(
+            out,
+            "{}: {}/{} ({:.1}%)",
+            self.path,
+            stats.selected,
+            stats.lines,
+            stats.percent_selected()
+        )
+    }
+
+    /// Writes the `--density` of the matches of the file, which always names
+    /// the file.
+    pub(crate) fn write_density(&self, out: &mut dyn Write, stats: &Stats) -> io::Result<()> {
+        writeln!(
+            out,
+            "{}: {:.1} matches per 100 lines, longest run {}",
+            self.path,
+            stats.percent_selected(),
+            stats.longest_run
+        )
+    }
+
+    /// Writes the `--percentile` of the lengths of the selected lines of the
+    /// file, which always names the file, if it has any.
+    pub(crate) fn write_percentile(
+        &self,
+        out: &mut dyn Write,
+        stats: &Stats,
+        percentile: f64,
+    ) -> io::Result<()> {
+        write_percentile(out, self.path, stats, percentile)
+    }
+
+    /// Writes the `--summary` of the file, which always names the file.
+    pub(crate) fn write_summary(&self, out: &mut dyn Write, stats: &Stats) -> io::Result<()> {
+        writeln!(out, "{}: {}", self.path, summary(stats))
+    }
+
+    /// Returns the number of characters (Unicode scalar values, not bytes)
+    /// of `text` as printed before it with `--char-count`, like `(42 chars) `,
+    /// or nothing without it.
+    fn char_count(&self, text: &str) -> String {
+        if self.config.char_count {
+            format!("({} chars) ", text.chars().count())
+        } else {
+            String::new()
+        }
+    }
+
+    /// Returns the `--timestamp` of a match found now, like
+    /// `[2024-01-02T03:04:05Z] `.
+    fn timestamp(&self) -> String {
+        if self.config.timestamp {
+            format!("[{}] ", self.config.clock.rfc3339())
+        } else {
+            String::new()
+        }
+    }
+
+    /// Returns the `--columns` of the match at `span` in `text`, as
+    /// `start-end:` with 1-based character positions and an inclusive end.
+    /// Lines without a match, with `-v`, have none.
+    fn columns(&self, text: &str, span: Option<(usize, usize)>) -> String {
+        match span {
+            Some((start, end)) if self.config.columns => {
+                let first = text[..start].chars().count() + 1;
+                let last = first + text[start..end].chars().count() - 1;
+                format!("{first}-{last}:")
+            }
+            _ => String::new(),
+        }
+    }
+
+    /// Prepares `text` for display: shortens it to `--max-columns`
+    /// characters and highlights the byte ranges in `spans` with colors or
+    /// `--mark` markers, or dims the text around them with `--dim-context`.
+    fn display(&self, text: &str, spans: &[(usize, usize)]) -> String {
+        let cut = self
+            .config
+            .max_columns
+            .and_then(|max| text.char_indices().nth(max))
+            .map(|(end, _)| end);
+        let visible = &text[..cut.unwrap_or(text.len())];
+
+        let dim = self.color && self.config.dim_context;
+        let markers = if dim {
+            None
+        } else if self.color {
+            Some((COLOR_MATCH, COLOR_RESET))
+        } else if self.config.mark {
+            Some((MARK_OPEN, MARK_CLOSE))
+        } else {
+            None
+        };
+
+        let mut shown = String::new();
+        let mut last = 0;
+
+        if let Some((open, close)) = markers {
+            for &(start, end) in spans {
+                if start >= visible.len() {
+                    break;
+                }
+
+                let end = end.min(visible.len());
+                shown.push_str(&self.escape(&visible[last..start]));
+                shown.push_str(open);
+                shown.push_str(&self.escape(&visible[start..end]));
+                shown.push_str(close);
+                last = end;
+            }
+        } else if dim {
+            for &(start, end) in spans {
+                if start >= visible.len() {
+                    break;
+                }
+
+                let end = end.min(visible.len());
+                push_dimmed(&mut shown, &self.escape(&visible[last..start]));
+                shown.push_str(&self.escape(&visible[start..end]));
+                last = end;
+            }
+        }
+
+        if dim {
+            push_dimmed(&mut shown, &self.escape(&visible[last..]));
+        } else {
+            shown.push_str(&self.escape(&visible[last..]));
+        }
+
+        if cut.is_some() {
+            shown.push('…');
+        }
+
+        shown
+    }
+
+    /// Makes the non-printing characters of `text` visible with
+    /// `--escape-nonprinting`, see [`escape_nonprinting`].
+    fn escape<'t>(&self, text: &'t str) -> Cow<'t, str> {
+        if self.config.escape_nonprinting && text.chars().any(is_nonprinting) {
+            Cow::Owned(escape_nonprinting(text))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// Builds the `path:lineno:byteoffset:` prefix enabled by multiple
+    /// files, `-n` and `-b`, with `separator` after each field.
+    fn prefix(&self, number: usize, offset: usize, separator: char) -> String {
+        let mut prefix = self.prefix_filename(separator);
+
+        if self.config.line_number {
+            let _ = write!(prefix, "{number}{separator}");
+        }
+
+        if self.config.byte_offset {
+            let _ = write!(prefix, "{offset}{separator}");
+        }
+
+        prefix
+    }
+
+    /// Returns `path` followed by `separator` when file names are shown.
+    fn prefix_filename(&self, separator: char) -> String {
+        if self.show_filename {
+            format!("{}{separator}", self.path)
+        } else {
+            String::new()
+        }
+    }
+}
+
+/// Returns `true` if `c` is a control character other than a tab.
+fn is_nonprinting(c: char) -> bool {
+    c.is_control() && c != '\t'
+}
+
+/// Replaces the non-printing characters of `text` with visible escapes like
+/// `cat -v`: the ASCII control characters in caret notation (`^[` for
+/// escape, `^M` for a carriage return, `^?` for delete), and the other
+/// control characters as `\u{85}`. Tabs are kept.
+fn escape_nonprinting(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+
+    for c in text.chars() {
+        match c {
+            '\t' => escaped.push(c),
+            '\0'..='\x1f' => {
+                escaped.push('^');
+                escaped.push(char::from(c as u8 + b'@'));
+            }
+            '\x7f' => escaped.push_str("^?"),
+            c if c.is_control() => {
+                let _ = write!(escaped, "\\u{{{:x}}}", u32::from(c));
+            }
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// Appends `text` to `shown` in faint colors, unless it's empty.
+fn push_dimmed(shown: &mut String, text: &str) {
+    if !text.is_empty() {
+        shown.push_str(COLOR_DIM);
+        shown.push_str(text);
+        shown.push_str(COLOR_RESET);
+    }
+}
+
+/// Describes the line and byte counts of `stats` for `--summary`, like
+/// `7 lines, 128 bytes, 4 matching lines, 73 matching bytes`.
+pub(crate) fn summary(stats: &Stats) -> String {
+    format!(
+        "{} lines, {} bytes, {} matching lines, {} matching bytes",
+        stats.lines, stats.bytes, stats.selected, stats.selected_bytes
+    )
+}
+
+/// The `--output` file, compressed with gzip with `--compress`.
+pub(crate) enum OutputFile {
+    Plain(File),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<File>),
+}
+
+impl OutputFile {
+    /// Wraps `file`, compressing what is written to it if `compress` is set.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error for `compress` without the `gzip` feature.
+    pub(crate) fn new(file: File, compress: bool) -> io::Result<OutputFile> {
+        if !compress {
+            return Ok(OutputFile::Plain(file));
+        }
+
+        #[cfg(feature = "gzip")]
+        return Ok(OutputFile::Gzip(flate2::write::GzEncoder::new(
+            file,
+            flate2::Compression::default(),
+        )));
+
+        #[cfg(not(feature = "gzip"))]
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "--compress requires the `gzip` feature",
+        ))
+    }
+
+    /// Writes the end of the gzip stream with `--compress`. Unlike when the
+    /// file is just dropped, errors writing it are returned.
+    pub(crate) fn finish(self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(_) => Ok(()),
+            #[cfg(feature = "gzip")]
+            OutputFile::Gzip(encoder) => encoder.finish().map(drop),
+        }
+    }
+}
+
+impl Write for OutputFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            OutputFile::Plain(file) => file.write(buf),
+            #[cfg(feature = "gzip")]
+            OutputFile::Gzip(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            OutputFile::Plain(file) => file.flush(),
+            #[cfg(feature = "gzip")]
+            OutputFile::Gzip(encoder) => encoder.flush(),
+        }
+    }
+}
+
+/// Describes the search of all files, which took `elapsed`, as a JSON
+/// object for `--summary-json`, like
+/// `{"files_scanned":2,"files_matched":1,"total_matches":4,"elapsed_ms":3}`.
+pub(crate) fn summary_json(total: &Stats, elapsed: Duration) -> String {
+    format!(
+        r#"{{"files_scanned":{},"files_matched":{},"total_matches":{},"elapsed_ms":{}}}"#,
+        total.files,
+        total.matched_files,
+        total.selected,
+        elapsed.as_millis()
+    )
+}
+
+/// Returns `true` if matches are highlighted with colors, as chosen with
+/// `--color`.
+pub(crate) fn use_color(config: &Config) -> bool {
+    match config.color {
+        ColorChoice::Never => false,
+        ColorChoice::Always => true,
+        ColorChoice::Auto => config.output.is_none() && io::stdout().is_terminal(),
+    }
+}
+
+/// How many characters are shown before and after each `--slurp` match.
+pub(crate) const SNIPPET_CONTEXT: usize = 20;
+
+/// The default width of the `--length-histogram` buckets.
+pub(crate) const DEFAULT_BUCKET_SIZE: usize = 10;
+
+/// The length of the longest bar of the `--length-histogram`.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Writes the `--percentile` of the lengths of the selected lines in
+/// `stats`, labeled `name`, like `a.txt: p95 line length 42`, unless there
+/// are none.
+pub(crate) fn write_percentile(
+    out: &mut dyn Write,
+    name: &str,
+    stats: &Stats,
+    percentile: f64,
+) -> io::Result<()> {
+    match stats.length_percentile(percentile) {
+        Some(length) => writeln!(out, "{name}: p{percentile} line length {length}"),
+        None => Ok(()),
+    }
+}
+
+/// Writes the `--length-histogram` of `lengths`, the number of lines of each
+/// length, in buckets of `bucket_size` lengths.
+///
+/// Each bucket from the shortest to the longest line gets a row, such as
+/// `10-19 | ####### 7`, with bars scaled to the largest bucket. Nothing is
+/// written without any lines.
+pub(crate) fn write_histogram(
+    out: &mut dyn Write,
+    lengths: &BTreeMap<usize, usize>,
+    bucket_size: usize,
+) -> io::Result<()> {
+    let (Some(&first), Some(&last)) = (lengths.keys().next(), lengths.keys().next_back()) else {
+        return Ok(());
+    };
+
+    let mut buckets = vec![0; last / bucket_size - first / bucket_size + 1];
+    for (length, count) in lengths {
+        buckets[length / bucket_size - first / bucket_size] += count;
+    }
+
+    let labels: Vec<String> = (first / bucket_size..=last / bucket_size)
+        .map(|bucket| {
+            let start = bucket * bucket_size;
+            format!("{start}-{}", start + bucket_size - 1)
+        })
+        .collect();
+    let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+    let largest = buckets.iter().copied().max().unwrap_or(0);
+
+    for (label, count) in labels.iter().zip(buckets) {
+        let mut bar = "#".repeat((count * HISTOGRAM_WIDTH).div_ceil(largest));
+        if !bar.is_empty() {
+            bar.push(' ');
+        }
+        writeln!(out, "{label:>label_width$} | {bar}{count}")?;
+    }
+
+    Ok(())
+}
+
+/// Returns where the output of searching `file` is written with
+/// `--split-output dir`: `file` below `dir`, with `.matches` appended.
+///
+/// Only the normal components of `file` are kept, so absolute paths and
+/// paths with `..` still end up below `dir`.
+///
+/// # Examples
+///
+/// ```
+/// use std::path::Path;
+///
+/// use simple_grep::split_output_path;
+///
+/// assert_eq!(
+///     split_output_path(Path::new("out"), Path::new("logs/app.log")),
+///     Path::new("out/logs/app.log.matches")
+/// );
+/// assert_eq!(
+///     split_output_path(Path::new("out"), Path::new("/var/../app.log")),
+///     Path::new("out/var/app.log.matches")
+/// );
+/// ```
+pub fn split_output_path(dir: &Path, file: &Path) -> PathBuf {
+    let mut path = dir.to_path_buf();
+
+    for component in file.components() {
+        if let Component::Normal(name) = component {
+            path.push(name);
+        }
+    }
+
+    let mut path = path.into_os_string();
+    path.push(".matches");
+    path.into()
+}
+
+/// The header row written before `--csv` output with `--csv-header`.
+pub(crate) const CSV_HEADER: &str = "path,line_number,text";
+
+/// Quotes `field` for a CSV row if it contains a comma, a quote or a line
+/// break, doubling any quotes inside it.
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+/// Quotes `text` as a JSON string, escaping quotes, backslashes and control
+/// characters.
+pub(crate) fn json_string(text: &str) -> String {
+    let mut quoted = String::with_capacity(text.len() + 2);
+    quoted.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            // Writing to a `String` can't fail.
+            c if (c as u32) < 0x20 => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+
+    quoted.push('"');
+    quoted
+}
+
+/// Returns the context `lines` of a `--json` match as a JSON array, like
+/// `[{"line_number":3,"text":"WARN: retrying"}]`.
+fn json_context_lines(lines: &[OwnedLine]) -> String {
+    let objects: Vec<String> = lines
+        .iter()
+        .map(|line| {
+            format!(
+                "{{\"line_number\":{},\"text\":{}}}",
+                line.number,
+                json_string(&line.text)
+            )
+        })
+        .collect();
+
+    format!("[{}]", objects.join(","))
+}
+
+/// Holds back the line break at the end of each write until more output
+/// follows, for `--no-trailing-newline`, so the one at the very end of the
+/// output is never written.
+pub(crate) struct NoTrailingNewline<W: Write> {
+    inner: W,
+    /// The line break held back, `\n` or `\r\n`, if any.
+    pending: &'static [u8],
+}
+
+impl<W: Write> NoTrailingNewline<W> {
+    pub(crate) fn new(inner: W) -> NoTrailingNewline<W> {
+        NoTrailingNewline {
+            inner,
+            pending: b"",
+        }
+    }
+}
+
+impl<W: Write> Write for NoTrailingNewline<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        self.inner.write_all(self.pending)?;
+
+        let (text, pending): (&[u8], &'static [u8]) = if let Some(text) = buf.strip_suffix(b"\r\n")
+        {
+            (text, b"\r\n")
+        } else if let Some(text) = buf.strip_suffix(b"\n") {
+            (text, b"\n")
+        } else {
+            (buf, b"")
+        };
+        self.inner.write_all(text)?;
+        self.pending = pending;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writes the lines written to it as the elements of a JSON array, for
+/// `--json-array`.
+///
+/// The opening bracket and the commas between elements are only written
+/// once a line is complete, so the output is a well-formed array however the
+/// lines are written, and `[]` if there are none.
+pub(crate) struct JsonArray<W: Write> {
+    inner: W,
+    /// The incomplete line written so far.
+    line: Vec<u8>,
+    /// Whether no element has been written yet.
+    empty: bool,
+}
+
+impl<W: Write> JsonArray<W> {
+    pub(crate) fn new(inner: W) -> JsonArray<W> {
+        JsonArray {
+            inner,
+            line: Vec::new(),
+            empty: true,
+        }
+    }
+
+    /// Closes the array. An incomplete last line is written as an element.
+    pub(crate) fn finish(mut self) -> io::Result<()> {
+        if !self.line.is_empty() {
+            self.write_element()?;
+        }
+
+        if self.empty {
+            writeln!(self.inner, "[]")
+        } else {
+            writeln!(self.inner, "\n]")
+        }
+    }
+
+    fn write_element(&mut self) -> io::Result<()> {
+        let separator: &[u8] = if self.empty { b"[\n" } else { b",\n" };
+        self.inner.write_all(separator)?;
+        self.inner.write_all(&self.line)?;
+        self.line.clear();
+        self.empty = false;
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for JsonArray<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut rest = buf;
+
+        while let Some(end) = rest.iter().position(|&byte| byte == b'\n') {
+            self.line.extend_from_slice(&rest[..end]);
+            self.write_element()?;
+            rest = &rest[end + 1..];
+        }
+
+        self.line.extend_from_slice(rest);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// A custom output format for matches, set with `--format`.
+///
+/// The template is printed once per match with these placeholders replaced:
+///
+/// * `{path}` - the path of the file.
+/// * `{lineno}` - the 1-based line number.
+/// * `{text}` - the matching line, or the match itself with `-o`.
+/// * `{count}` - how many times the query occurs in the line.
+///
+/// Literal braces are written as `{{` and `}}`.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::Template;
+///
+/// assert!("{path}|{lineno}|{text}".parse::<Template>().is_ok());
+/// assert!("{path}|{column}".parse::<Template>().is_err());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    parts: Vec<Part>,
+}
+
+/// A piece of a [`Template`].
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Path,
+    LineNumber,
+    Text,
+    Count,
+}
+
+/// The values a [`Template`] is rendered with.
+pub(crate) struct Record<'a> {
+    pub(crate) path: &'a str,
+    pub(crate) line_number: usize,
+    pub(crate) text: &'a str,
+    pub(crate) count: usize,
+}
+
+impl Template {
+    /// Renders the template for a single match.
+    pub(crate) fn render(&self, record: &Record) -> String {
+        let mut rendered = String::new();
+
+        for part in &self.parts {
+            // Writing to a `String` can't fail.
+            let _ = match part {
+                Part::Literal(text) => write!(rendered, "{text}"),
+                Part::Path => write!(rendered, "{}", record.path),
+                Part::LineNumber => write!(rendered, "{}", record.line_number),
+                Part::Text => write!(rendered, "{}", record.text),
+                Part::Count => write!(rendered, "{}", record.count),
+            };
+        }
+
+        rendered
+    }
+}
+
+impl FromStr for Template {
+    type Err = String;
+
+    /// Parses a template, rejecting unknown placeholders and unbalanced braces.
+    fn from_str(template: &str) -> Result<Template, String> {
+        let mut parts = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.as_str().starts_with('{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.as_str().starts_with('}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let rest = chars.as_str();
+                    let Some(end) = rest.find('}') else {
+                        return Err(format!("Unclosed '{{' in format template '{template}'"));
+                    };
+
+                    let part = match &rest[..end] {
+                        "path" => Part::Path,
+                        "lineno" => Part::LineNumber,
+                        "text" => Part::Text,
+                        "count" => Part::Count,
+                        name => {
+                            return Err(format!(
+                                "Unknown placeholder '{{{name}}}' in format template '{template}'"
+                            ));
+                        }
+                    };
+
+                    if !literal.is_empty() {
+                        parts.push(Part::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(part);
+                    chars = rest[end + 1..].chars();
+                }
+                '}' => return Err(format!("Unmatched '}}' in format template '{template}'")),
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            parts.push(Part::Literal(literal));
+        }
+
+        Ok(Template { parts })
+    }
+}
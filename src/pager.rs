@@ -0,0 +1,102 @@
+//! Showing the output in a pager for `--pager`.
+
+use std::io::{self, IsTerminal, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::{CancelFlag, Config};
+
+/// The pager used when stdout is a terminal and no `--pager` is given.
+pub(crate) const DEFAULT_PAGER: &str = "less -R";
+
+/// A pager process the output is written to.
+///
+/// Once the pager exits, such as when quitting `less` before the end, writes
+/// fail with a broken pipe. The pager then swallows the rest of the output
+/// and stops the search through its [`CancelFlag`].
+pub(crate) struct Pager {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    cancel: CancelFlag,
+    /// Whether the pager exited before all output was written to it.
+    closed: bool,
+}
+
+impl Pager {
+    /// Starts the pager for the output of `config`: the `--pager` command, or
+    /// `less -R`, split on whitespace. There is none unless stdout is a
+    /// terminal, nor with `--no-pager` or `--output`.
+    ///
+    /// If the pager can't be started, the output goes to stdout, with a
+    /// warning when the command was given with `--pager`.
+    pub(crate) fn spawn(config: &Config) -> Option<Pager> {
+        if config.no_pager || config.output.is_some() || !io::stdout().is_terminal() {
+            return None;
+        }
+
+        let command = config.pager.as_deref().unwrap_or(DEFAULT_PAGER);
+        let mut words = command.split_whitespace();
+        let program = words.next()?;
+
+        match Command::new(program)
+            .args(words)
+            .stdin(Stdio::piped())
+            .spawn()
+        {
+            Ok(mut child) => Some(Pager {
+                stdin: child.stdin.take(),
+                child,
+                cancel: config.cancel.clone(),
+                closed: false,
+            }),
+            Err(err) => {
+                if config.pager.is_some() {
+                    eprintln!("simple_grep: warning: can't run pager '{command}': {err}");
+                }
+                None
+            }
+        }
+    }
+
+    /// Closes the input of the pager and waits for the user to quit it.
+    /// Returns whether the pager exited before all output was written to it.
+    pub(crate) fn finish(mut self) -> io::Result<bool> {
+        drop(self.stdin.take());
+        self.child.wait()?;
+        Ok(self.closed)
+    }
+
+    /// Handles the result of writing to the pager: a broken pipe means it
+    /// exited, which stops the search.
+    fn handle<T>(&mut self, result: io::Result<T>, written: T) -> io::Result<T> {
+        match result {
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => {
+                self.closed = true;
+                self.cancel.cancel();
+                Ok(written)
+            }
+            result => result,
+        }
+    }
+}
+
+impl Write for Pager {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.stdin {
+            Some(stdin) if !self.closed => {
+                let result = stdin.write(buf);
+                self.handle(result, buf.len())
+            }
+            _ => Ok(buf.len()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.stdin {
+            Some(stdin) if !self.closed => {
+                let result = stdin.flush();
+                self.handle(result, ())
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,125 @@
+//! Regular expression matching.
+
+use regex::{Regex, RegexBuilder};
+
+use crate::normalize::normalize;
+use crate::{Config, Matcher};
+
+/// Matches the query as a regular expression, set with `-E` or `--regex`.
+///
+/// The syntax is that of the [`regex`] crate. A pattern that can match the
+/// empty string, such as `a*`, selects every line, but its empty matches are
+/// never reported as spans: after an empty match the search moves on by at
+/// least one character, and only the non-empty matches are printed with
+/// `-o` or highlighted, like in `grep`.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::{Matcher, RegexMatcher};
+///
+/// let matcher = RegexMatcher::new(r"\d+ms", false)?;
+///
+/// assert_eq!(vec![(5, 10)], matcher.find_spans("took 250ms"));
+/// assert!(!matcher.is_match("took long"));
+/// # Ok::<(), regex::Error>(())
+/// ```
+pub struct RegexMatcher {
+    regex: Regex,
+}
+
+impl RegexMatcher {
+    /// Compiles `pattern`, ignoring case with `ignore_case`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if `pattern` isn't a valid regular expression.
+    pub fn new(pattern: &str, ignore_case: bool) -> Result<RegexMatcher, regex::Error> {
+        let regex = RegexBuilder::new(pattern)
+            .case_insensitive(ignore_case)
+            .build()?;
+
+        Ok(RegexMatcher { regex })
+    }
+}
+
+impl Matcher for RegexMatcher {
+    fn find_spans(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut spans = Vec::new();
+        let mut start = 0;
+
+        while let Some(found) = self.regex.find_at(line, start) {
+            if found.is_empty() {
+                // Step over the next character so the search makes progress.
+                match line[found.end()..].chars().next() {
+                    Some(c) => start = found.end() + c.len_utf8(),
+                    None => break,
+                }
+            } else {
+                spans.push((found.start(), found.end()));
+                start = found.end();
+            }
+        }
+
+        spans
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.regex.is_match(line)
+    }
+}
+
+/// Returns the byte ranges of the non-empty matches of the queries of
+/// `config` in `contents` as a whole, for `--multiline`.
+///
+/// `^` and `$` match at the start and end of every line, and with
+/// `multiline_dotall`, `.` matches line breaks too.
+///
+/// # Errors
+///
+/// Returns an error if the query isn't a valid regular expression.
+pub(crate) fn multiline_spans(
+    config: &Config,
+    contents: &str,
+) -> Result<Vec<(usize, usize)>, String> {
+    let regex = whole_contents_regex(config, true)?;
+
+    Ok(regex
+        .find_iter(contents)
+        .filter(|found| !found.is_empty())
+        .map(|found| (found.start(), found.end()))
+        .collect())
+}
+
+/// Returns `true` if the queries of `config` match `contents` as a whole,
+/// for `--whole-file-match`. `^` and `$` only match at the start and end of
+/// `contents`.
+///
+/// # Errors
+///
+/// Returns an error if the query isn't a valid regular expression.
+pub(crate) fn matches_whole_file(config: &Config, contents: &str) -> Result<bool, String> {
+    Ok(whole_contents_regex(config, false)?.is_match(contents))
+}
+
+/// Compiles the queries of `config`, as alternatives, for matching against the contents of a
+/// file at once, with `^` and `$` matching at every line with `multi_line`.
+fn whole_contents_regex(config: &Config, multi_line: bool) -> Result<Regex, String> {
+    let pattern = match config.queries() {
+        [query] => normalize(config.normalize, query),
+        queries => {
+            let queries: Vec<String> = queries
+                .iter()
+                .map(|query| format!("(?:{})", normalize(config.normalize, query)))
+                .collect();
+            queries.join("|").into()
+        }
+    };
+
+    RegexBuilder::new(&pattern)
+        .case_insensitive(config.ignore_case)
+        .multi_line(multi_line)
+        .dot_matches_new_line(config.multiline_dotall)
+        .build()
+        .map_err(|err| format!("Invalid regular expression '{pattern}': {err}"))
+}
@@ -0,0 +1,50 @@
+//! Random sampling of matches for `--sample`.
+
+/// A uniformly random sample of at most `size` items of a stream, chosen in a
+/// single pass with reservoir sampling.
+pub(crate) struct Reservoir<T> {
+    size: usize,
+    /// Number of items offered so far.
+    seen: usize,
+    /// The chosen items, with the position at which they were offered.
+    items: Vec<(usize, T)>,
+    rng: fastrand::Rng,
+}
+
+impl<T> Reservoir<T> {
+    /// Creates an empty sample of `size` items, seeded with `seed` for
+    /// reproducible samples or randomly otherwise.
+    pub(crate) fn new(size: usize, seed: Option<u64>) -> Reservoir<T> {
+        Reservoir {
+            size,
+            seen: 0,
+            // The vector grows as items are chosen, so a large `size` costs
+            // nothing unless that many are offered.
+            items: Vec::new(),
+            rng: seed.map_or_else(fastrand::Rng::new, fastrand::Rng::with_seed),
+        }
+    }
+
+    /// Offers the next item of the stream. `item` is only called if the item
+    /// is chosen, replacing a previously chosen one once the sample is full.
+    pub(crate) fn offer_with(&mut self, item: impl FnOnce() -> T) {
+        let position = self.seen;
+        self.seen += 1;
+
+        if self.items.len() < self.size {
+            self.items.push((position, item()));
+        } else {
+            let index = self.rng.usize(..self.seen);
+
+            if index < self.size {
+                self.items[index] = (position, item());
+            }
+        }
+    }
+
+    /// Returns the chosen items in the order they were offered.
+    pub(crate) fn into_items(mut self) -> impl Iterator<Item = T> {
+        self.items.sort_unstable_by_key(|&(position, _)| position);
+        self.items.into_iter().map(|(_, item)| item)
+    }
+}
@@ -0,0 +1,553 @@
+//! Line matching.
+
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+
+use crate::json_input;
+use crate::matcher::{Case, LiteralMatcher, line_matches};
+use crate::min_length::MinLengthMatcher;
+#[cfg(feature = "aho-corasick")]
+use crate::multi::MultiLiteral;
+use crate::normalize::normalize;
+use crate::squeeze::{SqueezedMatcher, squeeze};
+use crate::variants::CaseVariantsMatcher;
+use crate::word::WordMatcher;
+use crate::{Config, FuzzyMatcher, Matcher};
+
+/// The query of a [`Config`], prepared for matching lines.
+pub(crate) struct Query {
+    /// One matcher per query, see [`Config::queries`].
+    matchers: Vec<Box<dyn Matcher + Send + Sync>>,
+    /// Matches all the queries at once, when there are many literal ones.
+    #[cfg(feature = "aho-corasick")]
+    any: Option<MultiLiteral>,
+    /// Select the lines that don't match instead (`-v`).
+    invert: bool,
+    /// Report touching matches as one (`--merge-adjacent`).
+    merge_adjacent: bool,
+    /// Matches the first line of each `--from`/`--to` range.
+    from: Option<LiteralMatcher>,
+    /// Matches the last line of each `--from`/`--to` range.
+    to: Option<LiteralMatcher>,
+    /// Keeps only the selected lines it matches (`--also-matching`).
+    also: Option<Box<dyn Matcher + Send + Sync>>,
+    /// Drops the selected lines it matches (`--exclude-matching`).
+    exclude: Option<Box<dyn Matcher + Send + Sync>>,
+    /// Match the string values of JSON lines only (`--json-input`).
+    json_input: bool,
+    /// Match lines that aren't JSON objects as a whole with `json_input`,
+    /// rather than never selecting them (`--match-invalid-json`).
+    match_invalid_json: bool,
+    /// The label of each query, with `--label-patterns`.
+    labels: Option<Vec<String>>,
+    /// Lines that are never selected, as keys from [`Query::line_key`], for
+    /// `--only-in`.
+    skip: HashSet<String>,
+    /// Compare lines ignoring case in `line_key` (`-i`).
+    ignore_case: bool,
+}
+
+impl Query {
+    /// Builds the matcher selected by the options of `config`.
+    ///
+    /// Fails if the query isn't a valid regular expression with `regex`, if
+    /// `normalize` is set without the `normalize` feature, or if `json_input`
+    /// is set without the `json` feature.
+    ///
+    /// With `normalize`, the query and the other patterns are normalized; the
+    /// lines must be normalized by the caller.
+    pub(crate) fn new(config: &Config) -> Result<Query, String> {
+        if config.normalize.is_some() && !cfg!(feature = "normalize") {
+            return Err(String::from(
+                "Unicode normalization requires the `normalize` feature",
+            ));
+        }
+
+        if config.json_input && !cfg!(feature = "json") {
+            return Err(String::from("--json-input requires the `json` feature"));
+        }
+
+        let case = if !config.ignore_case {
+            Case::Sensitive
+        } else if config.ascii_case {
+            Case::AsciiInsensitive
+        } else {
+            Case::Insensitive
+        };
+
+        let mut matchers = Vec::new();
+        let mut labels = Vec::new();
+        // The queries as they are matched, without labels and normalized.
+        #[cfg(feature = "aho-corasick")]
+        let mut patterns = Vec::new();
+
+        for query in config.queries() {
+            // With `--label-patterns`, a query of the form `name=pattern` is
+            // labeled `name`, and any other with its own text.
+            let (label, query) = match query.split_once('=') {
+                Some((label, pattern)) if config.label_patterns => (label, pattern),
+                _ => (query.as_str(), query.as_str()),
+            };
+            labels.push(label.to_string());
+
+            let mut query = normalize(config.normalize, query);
+            if config.squeeze_whitespace {
+                query = squeeze(&query).into();
+            }
+            #[cfg(feature = "aho-corasick")]
+            patterns.push(query.to_string());
+
+            let mut matcher: Box<dyn Matcher + Send + Sync> = match config.fuzzy {
+                _ if config.regex => regex_matcher(&query, config.ignore_case)?,
+                Some(max_distance) => {
+                    Box::new(FuzzyMatcher::new(&query, max_distance, config.ignore_case))
+                }
+                None if config.case_variants && !config.ignore_case => {
+                    Box::new(CaseVariantsMatcher::new(&query))
+                }
+                None => Box::new(LiteralMatcher::new(&query, case)),
+            };
+            if config.squeeze_whitespace {
+                matcher = Box::new(SqueezedMatcher::new(matcher));
+            }
+            if config.word_regexp || config.word_start {
+                matcher = Box::new(WordMatcher::new(matcher, config.word_regexp));
+            }
+            if config.min_match_len > 0 {
+                matcher = Box::new(MinLengthMatcher::new(matcher, config.min_match_len));
+            }
+            matchers.push(matcher);
+        }
+
+        Ok(Query {
+            #[cfg(feature = "aho-corasick")]
+            any: MultiLiteral::new(config, &patterns),
+            matchers,
+            invert: config.invert_match,
+            merge_adjacent: config.merge_adjacent,
+            from: config
+                .from
+                .as_deref()
+                .map(|from| LiteralMatcher::new(&normalize(config.normalize, from), case)),
+            to: config
+                .to
+                .as_deref()
+                .map(|to| LiteralMatcher::new(&normalize(config.normalize, to), case)),
+            also: config
+                .also_matching
+                .as_deref()
+                .map(|pattern| secondary_matcher(config, pattern, case))
+                .transpose()?,
+            exclude: config
+                .exclude_matching
+                .as_deref()
+                .map(|pattern| secondary_matcher(config, pattern, case))
+                .transpose()?,
+            json_input: config.json_input,
+            match_invalid_json: config.match_invalid_json,
+            labels: config.label_patterns.then_some(labels),
+            skip: HashSet::new(),
+            ignore_case: config.ignore_case,
+        })
+    }
+
+    /// Never selects the lines with one of the keys in `skip`, see
+    /// [`Query::line_key`].
+    pub(crate) fn skipping(self, skip: HashSet<String>) -> Query {
+        Query { skip, ..self }
+    }
+
+    /// Returns the key by which `line` is compared with other lines for
+    /// `--only-in`: the line itself, or lowercased with `-i`.
+    pub(crate) fn line_key<'l>(&self, line: &'l str) -> Cow<'l, str> {
+        if self.ignore_case {
+            Cow::Owned(line.to_lowercase())
+        } else {
+            Cow::Borrowed(line)
+        }
+    }
+
+    /// Returns `true` if searching starts at the first line of a file,
+    /// i.e. unless a `--from` pattern has to match first.
+    pub(crate) fn starts_in_range(&self) -> bool {
+        self.from.is_none()
+    }
+
+    /// Returns `true` if lines are only selected within `--from`/`--to`
+    /// ranges.
+    pub(crate) fn has_range(&self) -> bool {
+        self.from.is_some() || self.to.is_some()
+    }
+
+    /// Returns `true` if `line` starts a `--from`/`--to` range.
+    pub(crate) fn starts_range(&self, line: &str) -> bool {
+        self.from.as_ref().is_some_and(|from| from.is_match(line))
+    }
+
+    /// Returns `true` if `line` ends a `--from`/`--to` range.
+    pub(crate) fn ends_range(&self, line: &str) -> bool {
+        self.to.as_ref().is_some_and(|to| to.is_match(line))
+    }
+
+    /// Returns `true` if `line` is selected for output: if it matches any of
+    /// the queries or, with `-v`, if it doesn't, and it matches the
+    /// `--also-matching` pattern but not the `--exclude-matching` one.
+    ///
+    /// With `--json-input`, the patterns are only matched against the string
+    /// values of the line, see [`Query::texts`]. Lines skipped with
+    /// [`Query::skipping`] are never selected.
+    pub(crate) fn selects(&self, line: &str) -> bool {
+        let Some(texts) = self.texts(line) else {
+            return false;
+        };
+        let any_text =
+            |matcher: &(dyn Matcher + Send + Sync)| texts.iter().any(|text| matcher.is_match(text));
+
+        let matches = |text: &str| {
+            #[cfg(feature = "aho-corasick")]
+            if let Some(any) = &self.any {
+                return any.is_match(text);
+            }
+
+            self.matchers.iter().any(|matcher| matcher.is_match(text))
+        };
+        let matched = texts.iter().any(|text| matches(text));
+
+        matched != self.invert
+            && self.also.as_deref().is_none_or(any_text)
+            && !self.exclude.as_deref().is_some_and(any_text)
+            && (self.skip.is_empty() || !self.skip.contains(self.line_key(line).as_ref()))
+    }
+
+    /// Returns the texts of `line` that the queries and the `--also-matching`
+    /// and `--exclude-matching` patterns are matched against: the line
+    /// itself or, with `--json-input`, its string values. A line that isn't
+    /// a JSON object then has none, so it's never selected, unless
+    /// `--match-invalid-json` is set to match it as a whole.
+    fn texts<'l>(&self, line: &'l str) -> Option<Vec<Cow<'l, str>>> {
+        if !self.json_input {
+            return Some(vec![Cow::Borrowed(line)]);
+        }
+
+        match json_input::string_values(line) {
+            Some(values) => Some(values.into_iter().map(Cow::Owned).collect()),
+            None if self.match_invalid_json => Some(vec![Cow::Borrowed(line)]),
+            None => None,
+        }
+    }
+
+    /// Returns the indices of the queries that match `line`, in the order of
+    /// [`Config::queries`], matching the same texts as [`Query::selects`].
+    pub(crate) fn matching<'l>(&'l self, line: &'l str) -> impl Iterator<Item = usize> + 'l {
+        let texts = self.texts(line).unwrap_or_default();
+
+        self.matchers
+            .iter()
+            .enumerate()
+            .filter(move |(_, matcher)| texts.iter().any(|text| matcher.is_match(text)))
+            .map(|(index, _)| index)
+    }
+
+    /// Returns the labels of the queries that match `line` with
+    /// `--label-patterns`, like `[p1,p3]`, or `None` without it.
+    pub(crate) fn labels(&self, line: &str) -> Option<String> {
+        let labels = self.labels.as_ref()?;
+        let matching: Vec<&str> = self
+            .matching(line)
+            .map(|index| labels[index].as_str())
+            .collect();
+        Some(format!("[{}]", matching.join(",")))
+    }
+
+    /// Returns the byte ranges of the matches of the queries in `line`, from
+    /// left to right.
+    ///
+    /// Overlapping matches of different queries are merged, and so is, with
+    /// `--merge-adjacent`, a match that starts where the previous one ends.
+    pub(crate) fn spans(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut spans: Vec<(usize, usize)> = self
+            .matchers
+            .iter()
+            .flat_map(|matcher| matcher.find_spans(line))
+            .collect();
+
+        if self.matchers.len() > 1 {
+            spans.sort_unstable();
+        }
+
+        spans.dedup_by(|next, previous| {
+            let merged = next.0 < previous.1 || (self.merge_adjacent && next.0 == previous.1);
+            if merged {
+                previous.1 = previous.1.max(next.1);
+            }
+            merged
+        });
+
+        spans
+    }
+}
+
+/// Builds the matcher of a pattern that filters the selected lines: a regular
+/// expression with `regex`, and a literal otherwise (also with `--fuzzy`).
+fn secondary_matcher(
+    config: &Config,
+    pattern: &str,
+    case: Case,
+) -> Result<Box<dyn Matcher + Send + Sync>, String> {
+    let pattern = normalize(config.normalize, pattern);
+
+    if config.regex {
+        regex_matcher(&pattern, config.ignore_case)
+    } else {
+        Ok(Box::new(LiteralMatcher::new(&pattern, case)))
+    }
+}
+
+#[cfg(feature = "regex")]
+fn regex_matcher(
+    pattern: &str,
+    ignore_case: bool,
+) -> Result<Box<dyn Matcher + Send + Sync>, String> {
+    match crate::RegexMatcher::new(pattern, ignore_case) {
+        Ok(matcher) => Ok(Box::new(matcher)),
+        Err(err) => Err(format!("Invalid regular expression '{pattern}': {err}")),
+    }
+}
+
+#[cfg(not(feature = "regex"))]
+fn regex_matcher(_: &str, _: bool) -> Result<Box<dyn Matcher + Send + Sync>, String> {
+    Err(String::from(
+        "Regular expressions require the `regex` feature",
+    ))
+}
+
+/// A line of the input together with its position in the file.
+pub(crate) struct Line<'a> {
+    /// 1-based line number.
+    pub(crate) number: usize,
+    /// Byte offset of the start of the line from the start of the file.
+    pub(crate) offset: usize,
+    /// The line without its terminator.
+    pub(crate) text: &'a str,
+    /// Whether the line ended with `\r\n` rather than `\n`, or nothing at
+    /// the end of the file.
+    pub(crate) crlf: bool,
+}
+
+impl Line<'_> {
+    /// Copies the line, to keep it after the text it borrows is gone.
+    pub(crate) fn to_owned_line(&self) -> OwnedLine {
+        OwnedLine {
+            number: self.number,
+            offset: self.offset,
+            text: self.text.to_string(),
+            crlf: self.crlf,
+        }
+    }
+}
+
+/// A [`Line`] that owns its text, such as a line kept for context.
+pub(crate) struct OwnedLine {
+    pub(crate) number: usize,
+    pub(crate) offset: usize,
+    pub(crate) text: String,
+    pub(crate) crlf: bool,
+}
+
+impl OwnedLine {
+    pub(crate) fn as_line(&self) -> Line<'_> {
+        Line {
+            number: self.number,
+            offset: self.offset,
+            text: &self.text,
+            crlf: self.crlf,
+        }
+    }
+}
+
+/// Splits `contents` into lines like [`str::lines`], keeping track of
+/// their positions.
+pub(crate) fn lines(contents: &str) -> impl Iterator<Item = Line<'_>> {
+    let mut offset = 0;
+
+    contents
+        .split_inclusive('\n')
+        .enumerate()
+        .map(move |(index, raw)| {
+            let line = Line {
+                number: index + 1,
+                offset,
+                text: trim_line_terminator(raw),
+                crlf: raw.ends_with("\r\n"),
+            };
+            offset += raw.len();
+            line
+        })
+}
+
+/// The byte order mark some editors put at the start of UTF-8 files.
+pub(crate) const BOM: char = '\u{feff}';
+
+/// Removes a leading [`BOM`] from the line at the start of a file, for
+/// `--strip-bom`. The offset of the line moves past it, so it is still the
+/// offset of the text in the file.
+///
+/// Only the line at offset 0 can start with a BOM: its number may be higher
+/// with `--continuous-lineno`, and a U+FEFF at the start of a later line,
+/// such as the first one searched with `--seek-bytes`, is kept.
+pub(crate) fn strip_bom(line: Line<'_>) -> Line<'_> {
+    match line.text.strip_prefix(BOM) {
+        Some(text) if line.offset == 0 => Line {
+            offset: line.offset + BOM.len_utf8(),
+            text,
+            ..line
+        },
+        _ => line,
+    }
+}
+
+/// Performs a **case-sensitive** search of `query` in `contents`.
+///
+/// # Arguments
+///
+/// * `query` - The search string.
+/// * `contents` - The contents of the file as a string slice.
+///
+/// # Returns
+///
+/// A vector of lines that contain the query.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::search;
+///
+/// let query = "duct";
+/// let contents = "\
+/// Rust:
+/// safe, fast, productive.
+/// Pick three.
+/// Duct tape.";
+///
+/// assert_eq!(vec!["safe, fast, productive."], search(query, contents));
+/// ```
+pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| line_matches(query, line, Case::Sensitive))
+        .collect()
+}
+
+/// Performs a **case-insensitive** search of `query` in `contents`.
+///
+/// # Arguments
+///
+/// * `query` - The search string (case-insensitive).
+/// * `contents` - The contents of the file as a string slice.
+///
+/// # Returns
+///
+/// A vector of lines that contain the query, ignoring case.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::search_case_insensitive;
+///
+/// let query = "rUsT";
+/// let contents = "\
+/// Rust:
+/// safe, fast, productive.
+/// Pick three.
+/// Trust me.";
+///
+/// assert_eq!(vec!["Rust:", "Trust me."], search_case_insensitive(query, contents));
+/// ```
+pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let query = query.to_lowercase();
+    let mut results = Vec::new();
+
+    for line in contents.lines() {
+        if line_matches(&query, line, Case::Insensitive) {
+            results.push(line);
+        }
+    }
+
+    results
+}
+
+/// Performs a **case-insensitive** search of `query` in `contents`, folding
+/// case with ASCII rules only.
+///
+/// This is faster than [`search_case_insensitive`] because no lowercase copy
+/// of each line is allocated, but non-ASCII letters that differ only in case
+/// (such as `É` and `é`) are **not** considered equal.
+///
+/// # Arguments
+///
+/// * `query` - The search string (ASCII case-insensitive).
+/// * `contents` - The contents of the file as a string slice.
+///
+/// # Returns
+///
+/// A vector of lines that contain the query, ignoring ASCII case.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::search_ascii_case_insensitive;
+///
+/// let contents = "\
+/// Rust:
+/// Trust me.
+/// ÉTÉ
+/// été";
+///
+/// assert_eq!(vec!["Rust:", "Trust me."], search_ascii_case_insensitive("rUsT", contents));
+/// assert_eq!(vec!["été"], search_ascii_case_insensitive("été", contents));
+/// ```
+pub fn search_ascii_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| line_matches(query, line, Case::AsciiInsensitive))
+        .collect()
+}
+
+/// Removes a trailing `\n` or `\r\n` from `raw`.
+pub(crate) fn trim_line_terminator(raw: &str) -> &str {
+    let text = raw.strip_suffix('\n').unwrap_or(raw);
+    text.strip_suffix('\r').unwrap_or(text)
+}
+
+/// Counts how many times each distinct line occurs, like `uniq -c`.
+///
+/// Unlike `uniq`, the lines don't need to be adjacent. The result is sorted
+/// by count in descending order, and lines with equal counts are sorted
+/// lexicographically.
+///
+/// # Examples
+///
+/// ```
+/// use simple_grep::group_count;
+///
+/// let lines = ["ERROR: disk full", "ERROR: timeout", "ERROR: disk full"];
+///
+/// assert_eq!(
+///     vec![(2, "ERROR: disk full"), (1, "ERROR: timeout")],
+///     group_count(lines)
+/// );
+/// ```
+pub fn group_count<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<(usize, &'a str)> {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+
+    for line in lines {
+        *counts.entry(line).or_insert(0) += 1;
+    }
+
+    let mut groups: Vec<(usize, &str)> = counts
+        .into_iter()
+        .map(|(line, count)| (count, line))
+        .collect();
+    groups.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+    groups
+}
@@ -0,0 +1,79 @@
+//! Matching regardless of runs of whitespace, for `--squeeze-whitespace`.
+
+use crate::Matcher;
+
+/// Matches lines with their runs of whitespace collapsed to single spaces,
+/// reporting spans in the original line.
+///
+/// The query must be collapsed with [`squeeze`] too.
+pub(crate) struct SqueezedMatcher {
+    inner: Box<dyn Matcher + Send + Sync>,
+}
+
+impl SqueezedMatcher {
+    pub(crate) fn new(inner: Box<dyn Matcher + Send + Sync>) -> SqueezedMatcher {
+        SqueezedMatcher { inner }
+    }
+}
+
+impl Matcher for SqueezedMatcher {
+    fn find_spans(&self, line: &str) -> Vec<(usize, usize)> {
+        let squeezed = Squeezed::new(line);
+
+        self.inner
+            .find_spans(&squeezed.text)
+            .into_iter()
+            .map(|(start, end)| (squeezed.starts[start], squeezed.ends[end - 1]))
+            .collect()
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.inner.is_match(&squeeze(line))
+    }
+}
+
+/// Collapses each run of whitespace in `text` to a single space.
+pub(crate) fn squeeze(text: &str) -> String {
+    Squeezed::new(text).text
+}
+
+/// A line with its runs of whitespace collapsed to single spaces.
+struct Squeezed {
+    text: String,
+    /// The offset in the original line of each byte of `text`.
+    starts: Vec<usize>,
+    /// The offset in the original line just after each byte of `text`,
+    /// which is after the whole run for a collapsed space.
+    ends: Vec<usize>,
+}
+
+impl Squeezed {
+    fn new(line: &str) -> Squeezed {
+        let mut squeezed = Squeezed {
+            text: String::with_capacity(line.len()),
+            starts: Vec::with_capacity(line.len()),
+            ends: Vec::with_capacity(line.len()),
+        };
+
+        for (offset, c) in line.char_indices() {
+            let end = offset + c.len_utf8();
+
+            if !c.is_whitespace() {
+                squeezed.text.push(c);
+                squeezed.starts.extend(offset..end);
+                squeezed.ends.extend(offset + 1..=end);
+            } else if let (true, Some(run_end)) =
+                (squeezed.text.ends_with(' '), squeezed.ends.last_mut())
+            {
+                // Extend the run of whitespace collapsed so far.
+                *run_end = end;
+            } else {
+                squeezed.text.push(' ');
+                squeezed.starts.push(offset);
+                squeezed.ends.push(end);
+            }
+        }
+
+        squeezed
+    }
+}
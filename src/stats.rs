@@ -0,0 +1,150 @@
+//! Statistics gathered while searching.
+
+use std::collections::BTreeMap;
+use std::ops::AddAssign;
+use std::time::Duration;
+
+/// Line counts of a single file, or of several files added up.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Stats {
+    /// Number of lines read.
+    pub(crate) lines: usize,
+    /// Number of selected lines, i.e. matching lines, or non-matching lines
+    /// with `-v`.
+    pub(crate) selected: usize,
+    /// Number of bytes read, after decompression.
+    pub(crate) bytes: usize,
+    /// Number of bytes of the selected lines, without their line breaks, for
+    /// `--summary`.
+    pub(crate) selected_bytes: usize,
+    /// Number of files that couldn't be read, when added up over files.
+    pub(crate) failed: usize,
+    /// Number of selected lines of each length in characters, for
+    /// `--length-histogram` and `--percentile`.
+    pub(crate) lengths: BTreeMap<usize, usize>,
+    /// Number of selected lines matching each query, for
+    /// `--count-by-pattern` and `--report-unmatched`.
+    pub(crate) pattern_matches: Vec<usize>,
+    /// Number of selected lines in a row up to the last line, for
+    /// `--density`.
+    pub(crate) run: usize,
+    /// The most selected lines in a row, for `--density`. When added up
+    /// over files, the longest run of any of them.
+    pub(crate) longest_run: usize,
+    /// Number of files searched, when added up over files with
+    /// [`Stats::add_file`].
+    pub(crate) files: usize,
+    /// Number of searched files with at least one selected line.
+    pub(crate) matched_files: usize,
+    /// Number of searched files with a selected line matching each query,
+    /// for `--pattern-file-coverage`.
+    pub(crate) pattern_files: Vec<usize>,
+    /// Number of selected lines matching both queries of a pair, by their
+    /// indices, the lower one first, for `--cooccurrence`.
+    pub(crate) pattern_pairs: BTreeMap<(usize, usize), usize>,
+}
+
+impl Stats {
+    /// Counts a line, and whether it was selected.
+    pub(crate) fn record(&mut self, selected: bool) {
+        self.lines += 1;
+
+        if selected {
+            self.selected += 1;
+            self.run += 1;
+            self.longest_run = self.longest_run.max(self.run);
+        } else {
+            self.run = 0;
+        }
+    }
+
+    /// Adds the statistics of a searched `file`, counting it in `files` and,
+    /// if any of its lines were selected, in `matched_files` and the
+    /// `pattern_files` of the queries they matched.
+    pub(crate) fn add_file(&mut self, file: Stats) {
+        self.files += 1;
+
+        if file.selected > 0 {
+            self.matched_files += 1;
+        }
+
+        if self.pattern_files.len() < file.pattern_matches.len() {
+            self.pattern_files.resize(file.pattern_matches.len(), 0);
+        }
+
+        for (files, &count) in self.pattern_files.iter_mut().zip(&file.pattern_matches) {
+            if count > 0 {
+                *files += 1;
+            }
+        }
+
+        *self += file;
+    }
+
+    /// Returns the percentage of lines that were selected, or 0 for an
+    /// empty file.
+    pub(crate) fn percent_selected(&self) -> f64 {
+        if self.lines == 0 {
+            0.0
+        } else {
+            self.selected as f64 * 100.0 / self.lines as f64
+        }
+    }
+
+    /// Returns the `percentile` of the `lengths` with the nearest-rank
+    /// method: the smallest length that at least `percentile` percent of
+    /// the lines have at most, or `None` without lines.
+    pub(crate) fn length_percentile(&self, percentile: f64) -> Option<usize> {
+        let lines: usize = self.lengths.values().sum();
+        let rank = ((percentile / 100.0 * lines as f64).ceil() as usize).max(1);
+        let mut seen = 0;
+
+        self.lengths.iter().find_map(|(&length, &count)| {
+            seen += count;
+            (seen >= rank).then_some(length)
+        })
+    }
+
+    /// Returns the number of megabytes (of 10^6 bytes) read per second, if
+    /// they were read in `elapsed`.
+    pub(crate) fn megabytes_per_second(&self, elapsed: Duration) -> f64 {
+        self.bytes as f64 / 1e6 / elapsed.as_secs_f64().max(f64::EPSILON)
+    }
+}
+
+impl AddAssign for Stats {
+    fn add_assign(&mut self, other: Stats) {
+        self.lines += other.lines;
+        self.selected += other.selected;
+        self.bytes += other.bytes;
+        self.selected_bytes += other.selected_bytes;
+        self.failed += other.failed;
+        self.files += other.files;
+        self.matched_files += other.matched_files;
+        self.longest_run = self.longest_run.max(other.longest_run);
+
+        for (length, count) in other.lengths {
+            *self.lengths.entry(length).or_insert(0) += count;
+        }
+
+        for (pair, count) in other.pattern_pairs {
+            *self.pattern_pairs.entry(pair).or_insert(0) += count;
+        }
+
+        if self.pattern_matches.len() < other.pattern_matches.len() {
+            self.pattern_matches.resize(other.pattern_matches.len(), 0);
+        }
+
+        for (total, count) in self.pattern_matches.iter_mut().zip(other.pattern_matches) {
+            *total += count;
+        }
+
+        if self.pattern_files.len() < other.pattern_files.len() {
+            self.pattern_files.resize(other.pattern_files.len(), 0);
+        }
+
+        for (total, count) in self.pattern_files.iter_mut().zip(other.pattern_files) {
+            *total += count;
+        }
+    }
+}
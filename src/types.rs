@@ -0,0 +1,110 @@
+//! File types for `--type`: named sets of globs for the names of the files
+//! to search recursively.
+
+use std::path::Path;
+
+use crate::Config;
+use crate::walk::glob_matches;
+
+/// The built-in file types, by name, with the globs of their file names.
+const BUILTIN_TYPES: &[(&str, &[&str])] = &[
+    ("c", &["*.c", "*.h"]),
+    ("cpp", &["*.cpp", "*.cc", "*.cxx", "*.hpp", "*.hh", "*.h"]),
+    ("css", &["*.css"]),
+    ("go", &["*.go"]),
+    ("html", &["*.html", "*.htm"]),
+    ("java", &["*.java"]),
+    ("js", &["*.js", "*.mjs", "*.cjs"]),
+    ("json", &["*.json", "*.jsonl"]),
+    ("markdown", &["*.md", "*.markdown"]),
+    ("py", &["*.py", "*.pyi"]),
+    ("rust", &["*.rs"]),
+    ("sh", &["*.sh", "*.bash", "*.zsh"]),
+    ("toml", &["*.toml"]),
+    ("ts", &["*.ts", "*.tsx"]),
+    ("txt", &["*.txt"]),
+    ("yaml", &["*.yaml", "*.yml"]),
+];
+
+/// The globs of the `--type` file types, which the files found while
+/// searching recursively must match.
+pub(crate) struct FileTypes {
+    /// `None` without `--type`, to search all files.
+    globs: Option<Vec<String>>,
+    /// Whether the globs match regardless of case (`--glob-ignore-case`).
+    ignore_case: bool,
+}
+
+impl FileTypes {
+    /// Looks up the `--type` names of `config` among the built-in types and
+    /// those added with `--type-add NAME:GLOB`, which extend a built-in type
+    /// of the same name.
+    ///
+    /// Fails for an unknown type, or a `--type-add` without a `:`.
+    pub(crate) fn new(config: &Config) -> Result<FileTypes, String> {
+        let mut added = Vec::new();
+        for definition in &config.type_add {
+            match definition.split_once(':') {
+                Some((name, glob)) if !name.is_empty() && !glob.is_empty() => {
+                    added.push((name, glob));
+                }
+                _ => {
+                    return Err(format!(
+                        "invalid --type-add '{definition}': expected NAME:GLOB"
+                    ));
+                }
+            }
+        }
+
+        if config.file_types.is_empty() {
+            return Ok(FileTypes {
+                globs: None,
+                ignore_case: config.glob_ignore_case,
+            });
+        }
+
+        let mut globs = Vec::new();
+        for name in &config.file_types {
+            let builtin = BUILTIN_TYPES
+                .iter()
+                .find(|(builtin, _)| builtin == name)
+                .map(|(_, globs)| *globs);
+            let before = globs.len();
+
+            globs.extend(
+                builtin
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|glob| glob.to_string()),
+            );
+            globs.extend(
+                added
+                    .iter()
+                    .filter(|(added, _)| added == name)
+                    .map(|(_, glob)| glob.to_string()),
+            );
+
+            if globs.len() == before {
+                return Err(format!("unknown file type '{name}'"));
+            }
+        }
+
+        Ok(FileTypes {
+            globs: Some(globs),
+            ignore_case: config.glob_ignore_case,
+        })
+    }
+
+    /// Returns `true` if the file at `path` is searched: if its name matches
+    /// the globs of one of the types, or if there are none.
+    pub(crate) fn matches(&self, path: &Path) -> bool {
+        let Some(globs) = &self.globs else {
+            return true;
+        };
+
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        globs
+            .iter()
+            .any(|glob| glob_matches(glob, &name, self.ignore_case))
+    }
+}
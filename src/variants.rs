@@ -0,0 +1,74 @@
+//! Matching a few case variants of the query, for `--case-variants`.
+
+use crate::Matcher;
+use crate::matcher::{Case, LiteralMatcher};
+
+/// Matches the query as it is, lowercased, uppercased or in title case,
+/// each case-sensitively.
+///
+/// This finds `rust`, `RUST` and `Rust` for any of them, but not `rUsT`,
+/// and is cheaper than folding the case of every line.
+pub(crate) struct CaseVariantsMatcher {
+    variants: Vec<LiteralMatcher>,
+}
+
+impl CaseVariantsMatcher {
+    pub(crate) fn new(query: &str) -> CaseVariantsMatcher {
+        let variants = case_variants(query)
+            .iter()
+            .map(|variant| LiteralMatcher::new(variant, Case::Sensitive))
+            .collect();
+
+        CaseVariantsMatcher { variants }
+    }
+}
+
+impl Matcher for CaseVariantsMatcher {
+    fn find_spans(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut spans: Vec<_> = self
+            .variants
+            .iter()
+            .flat_map(|variant| variant.find_spans(line))
+            .collect();
+        spans.sort_by_key(|&(start, end)| (start, std::cmp::Reverse(end)));
+
+        // Keep the spans of different variants from overlapping.
+        let mut end = 0;
+        spans.retain(|span| {
+            let keep = span.0 >= end;
+            if keep {
+                end = span.1;
+            }
+            keep
+        });
+        spans
+    }
+
+    fn is_match(&self, line: &str) -> bool {
+        self.variants.iter().any(|variant| variant.is_match(line))
+    }
+}
+
+/// Returns the distinct case variants of `query`: itself, lowercased,
+/// uppercased and with the first letter of each word uppercased and the
+/// others lowercased.
+fn case_variants(query: &str) -> Vec<String> {
+    let mut title = String::with_capacity(query.len());
+    let mut word_start = true;
+    for c in query.chars() {
+        if word_start {
+            title.extend(c.to_uppercase());
+        } else {
+            title.extend(c.to_lowercase());
+        }
+        word_start = c.is_whitespace();
+    }
+
+    let mut variants = vec![query.to_string()];
+    for variant in [query.to_lowercase(), query.to_uppercase(), title] {
+        if !variants.contains(&variant) {
+            variants.push(variant);
+        }
+    }
+    variants
+}
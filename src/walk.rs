@@ -0,0 +1,179 @@
+//! Recursive directory traversal.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::CancelFlag;
+
+/// Calls `visit` with each file below `root`, depth first, with the entries of each
+/// directory sorted by name, byte by byte, so the order is the same on every
+/// run and every machine, whatever order `read_dir` lists them in.
+///
+/// Symbolic links are skipped unless `follow` is set. When following them,
+/// each directory is walked only once, however many links lead to it: a link
+/// to a directory that is already being walked (one of the ancestors of the
+/// link) is reported as a loop and skipped, so the walk always terminates,
+/// and a directory that was already walked through another path is skipped
+/// silently, so its files aren't searched twice.
+///
+/// Directories below `root` whose name matches one of the `exclude_dirs`
+/// globs, regardless of case with `glob_ignore_case`, are skipped without
+/// reading them.
+///
+/// Directories that can't be read are reported on stderr and skipped. The
+/// walk stops early once `cancel` is set, or once `visit` returns `false`,
+/// such as when enough files were found, without reading any more
+/// directories.
+pub(crate) fn visit_files(
+    root: &Path,
+    follow: bool,
+    exclude_dirs: &[String],
+    glob_ignore_case: bool,
+    cancel: &CancelFlag,
+    visit: &mut dyn FnMut(PathBuf) -> bool,
+) {
+    let mut walk = Walk {
+        follow,
+        exclude_dirs,
+        glob_ignore_case,
+        cancel,
+        ancestors: HashSet::new(),
+        visited: HashSet::new(),
+        visit,
+        done: false,
+    };
+    walk.dir(root);
+}
+
+/// The state of a walk below a directory.
+struct Walk<'a> {
+    follow: bool,
+    exclude_dirs: &'a [String],
+    glob_ignore_case: bool,
+    cancel: &'a CancelFlag,
+    /// The canonical paths of the directories currently being walked, from
+    /// the root down to the parent of the current directory.
+    ancestors: HashSet<PathBuf>,
+    /// The canonical paths of the directories walked so far, when following
+    /// symbolic links; without them, no directory can be reached twice.
+    visited: HashSet<PathBuf>,
+    visit: &'a mut dyn FnMut(PathBuf) -> bool,
+    /// Whether `visit` asked to stop.
+    done: bool,
+}
+
+impl Walk<'_> {
+    /// Visits the files below `dir`.
+    fn dir(&mut self, dir: &Path) {
+        let canonical = match fs::canonicalize(dir) {
+            Ok(canonical) => canonical,
+            Err(err) => return eprintln!("simple_grep: {}: {err}", dir.display()),
+        };
+
+        if !self.ancestors.insert(canonical.clone()) {
+            return eprintln!(
+                "simple_grep: warning: {}: recursive directory loop",
+                dir.display()
+            );
+        }
+
+        if self.follow && !self.visited.insert(canonical.clone()) {
+            self.ancestors.remove(&canonical);
+            return;
+        }
+
+        let mut entries: Vec<_> = match fs::read_dir(dir) {
+            Ok(entries) => entries
+                .filter_map(|entry| {
+                    entry
+                        .inspect_err(|err| eprintln!("simple_grep: {}: {err}", dir.display()))
+                        .ok()
+                })
+                .collect(),
+            Err(err) => {
+                self.ancestors.remove(&canonical);
+                return eprintln!("simple_grep: {}: {err}", dir.display());
+            }
+        };
+        entries.sort_by_key(|entry| entry.file_name());
+
+        for entry in entries {
+            if self.done || self.cancel.is_cancelled() {
+                break;
+            }
+
+            let path = entry.path();
+
+            let file_type = match entry.file_type() {
+                Ok(file_type) if file_type.is_symlink() && !self.follow => continue,
+                Ok(file_type) if file_type.is_symlink() => {
+                    fs::metadata(&path).map(|meta| meta.file_type())
+                }
+                other => other,
+            };
+
+            match file_type {
+                Ok(file_type) if file_type.is_dir() => {
+                    if !self.excluded(&entry.file_name().to_string_lossy()) {
+                        self.dir(&path);
+                    }
+                }
+                Ok(file_type) if file_type.is_file() => self.done = !(self.visit)(path),
+                Ok(_) => {}
+                Err(err) => eprintln!("simple_grep: {}: {err}", path.display()),
+            }
+        }
+
+        self.ancestors.remove(&canonical);
+    }
+
+    /// Returns `true` if the directory `name` is skipped with `--exclude-dir`.
+    fn excluded(&self, name: &str) -> bool {
+        self.exclude_dirs
+            .iter()
+            .any(|pattern| glob_matches(pattern, name, self.glob_ignore_case))
+    }
+}
+
+/// Returns `true` if `name` matches the glob `pattern`, in which `*` stands
+/// for any number of characters and `?` for a single one. With
+/// `ignore_case`, letters match regardless of case.
+pub(crate) fn glob_matches(pattern: &str, name: &str, ignore_case: bool) -> bool {
+    let fold = |text: &str| {
+        if ignore_case {
+            text.to_lowercase().chars().collect()
+        } else {
+            text.chars().collect::<Vec<char>>()
+        }
+    };
+    let pattern = fold(pattern);
+    let name = fold(name);
+    let (mut p, mut n) = (0, 0);
+    // The position of the last `*` in `pattern`, and where in `name` it
+    // started matching, to retry with it matching one more character.
+    let mut star = None;
+
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    star = Some((star_p, star_n + 1));
+                    p = star_p + 1;
+                    n = star_n + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == '*')
+}
@@ -0,0 +1,39 @@
+//! Restricting matches to word boundaries, for `-w` and `--word-start`.
+
+use crate::Matcher;
+
+/// Keeps the matches of another matcher that start at the start of a word
+/// and, for whole words, end at the end of one.
+///
+/// Words are runs of alphanumeric characters and `_`.
+pub(crate) struct WordMatcher {
+    inner: Box<dyn Matcher + Send + Sync>,
+    /// Whether matches must end at the end of a word too (`-w`), or can
+    /// continue into it (`--word-start`).
+    whole: bool,
+}
+
+impl WordMatcher {
+    pub(crate) fn new(inner: Box<dyn Matcher + Send + Sync>, whole: bool) -> WordMatcher {
+        WordMatcher { inner, whole }
+    }
+}
+
+impl Matcher for WordMatcher {
+    fn find_spans(&self, line: &str) -> Vec<(usize, usize)> {
+        self.inner
+            .find_spans(line)
+            .into_iter()
+            .filter(|&(start, end)| {
+                let starts_word = !line[..start].chars().next_back().is_some_and(is_word);
+                let ends_word = !line[end..].chars().next().is_some_and(is_word);
+                starts_word && (ends_word || !self.whole)
+            })
+            .collect()
+    }
+}
+
+/// Returns `true` if `c` is part of words.
+fn is_word(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
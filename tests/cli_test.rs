@@ -0,0 +1,3057 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/fixtures")
+        .join(name)
+}
+
+fn simple_grep() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_simple_grep"));
+    command
+        .env_remove("IGNORE_CASE")
+        .env_remove("MINIGREP_OPTIONS");
+    command
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
+
+#[test]
+fn env_options_enable_flags() {
+    let output = simple_grep()
+        .env("MINIGREP_OPTIONS", "-i -n")
+        .arg("rust")
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "\
+1:Rust is empowering everyone to build reliable and efficient software.
+5:RUST can be used for web development as well.
+6:Trust me, rUsT will be huge!
+"
+    );
+}
+
+#[test]
+fn env_options_reject_malformed_values() {
+    let output = simple_grep()
+        .env("MINIGREP_OPTIONS", "-i --bogus")
+        .arg("rust")
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unknown option '--bogus' (in MINIGREP_OPTIONS)"));
+}
+
+#[test]
+fn group_count_prints_counts() {
+    let output = simple_grep()
+        .args(["--group-count", "ERROR"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "   3  ERROR: disk full\n   1  ERROR: connection refused\n"
+    );
+
+    // The per-file summaries follow the groups.
+    let output = simple_grep()
+        .args(["--group-count", "-c", "--summary", "ERROR"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "   3  ERROR: disk full\n   1  ERROR: connection refused\n4\n\
+             {}: 7 lines, 128 bytes, 4 matching lines, 73 matching bytes\n\
+             total: 7 lines, 128 bytes, 4 matching lines, 73 matching bytes\n",
+            fixture("app.log").display()
+        )
+    );
+}
+
+#[test]
+fn only_matching_with_line_numbers_and_byte_offsets() {
+    let output = simple_grep()
+        .args(["-o", "-n", "-b", "-i", "rust"])
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "\
+1:0:Rust
+5:228:RUST
+6:275:rust
+6:284:rUsT
+"
+    );
+}
+
+#[test]
+fn continuous_lineno_numbers_lines_across_files() {
+    let log = fixture("app.log");
+    let languages = fixture("languages.txt");
+
+    let output = simple_grep()
+        .args(["-n", "--continuous-lineno", "-e", "disk", "-e", "Trust"])
+        .arg(&log)
+        .arg(&languages)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    // app.log has 7 lines, so line 6 of languages.txt is line 13.
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{log}:2:ERROR: disk full\n{log}:5:ERROR: disk full\n{log}:7:ERROR: disk full\n\
+             {languages}:13:Trust me, rUsT will be huge!\n",
+            log = log.display(),
+            languages = languages.display()
+        )
+    );
+}
+
+#[test]
+fn byte_offset_without_only_matching_reports_line_start() {
+    let output = simple_grep()
+        .args(["-b", "Trust"])
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert_eq!(stdout(&output), "274:Trust me, rUsT will be huge!\n");
+}
+
+fn sorted_lines(output: &Output) -> Vec<String> {
+    let mut lines: Vec<String> = stdout(output).lines().map(String::from).collect();
+    lines.sort();
+    lines
+}
+
+/// Creates `root/a.txt` and `root/sub/b.txt`, both containing "needle".
+fn create_tree(root: &Path) {
+    fs::create_dir(root.join("sub")).unwrap();
+    fs::write(root.join("a.txt"), "needle in a\nhay\n").unwrap();
+    fs::write(root.join("sub/b.txt"), "hay\nneedle in b\n").unwrap();
+}
+
+#[test]
+fn seek_bytes_starts_at_the_first_complete_line() {
+    let output = simple_grep()
+        .args(["--seek-bytes", "25", "-b", ":"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "39:WARN: retrying
+54:ERROR: connection refused
+80:ERROR: disk full
+97:INFO: cleanup
+111:ERROR: disk full
+"
+    );
+}
+
+#[test]
+fn include_partial_searches_the_line_seek_bytes_falls_into() {
+    let output = simple_grep()
+        .args(["--seek-bytes", "25", "--include-partial", "-b", "disk"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "25:OR: disk full\n80:ERROR: disk full\n111:ERROR: disk full\n"
+    );
+}
+
+#[test]
+fn max_files_searches_only_the_first_files_in_name_order() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("a")).unwrap();
+    for name in ["c.txt", "a/b.txt", "a/a.txt", "d.txt"] {
+        fs::write(dir.path().join(name), "needle\n").unwrap();
+    }
+
+    let output = simple_grep()
+        .args(["-r", "-c", "--max-files", "3", "needle"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}:1\n{}:1\n{}:1\n",
+            dir.path().join("a/a.txt").display(),
+            dir.path().join("a/b.txt").display(),
+            dir.path().join("c.txt").display()
+        )
+    );
+}
+
+#[test]
+fn max_files_cut_off_is_the_same_on_every_run() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("b")).unwrap();
+    for (name, contents) in [
+        ("e.txt", "needle 3\n"),
+        ("b/d.txt", "needle 2\n"),
+        ("a.txt", "needle 1\n"),
+        ("b/c.txt", "needle 1\n"),
+        ("f.txt", "needle 4\n"),
+    ] {
+        fs::write(dir.path().join(name), contents).unwrap();
+    }
+
+    let grep = || {
+        let output = simple_grep()
+            .args(["-r", "-c", "--skip-duplicate-content", "--max-files", "3"])
+            .arg("needle")
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    let expected = format!(
+        "{}:1\n{}:1\n{}:1\n",
+        dir.path().join("a.txt").display(),
+        dir.path().join("b/d.txt").display(),
+        dir.path().join("e.txt").display()
+    );
+    for _ in 0..3 {
+        assert_eq!(grep(), expected);
+    }
+}
+
+#[test]
+fn type_restricts_recursive_search_to_its_extensions() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("src")).unwrap();
+    fs::write(dir.path().join("src/main.rs"), "// needle\n").unwrap();
+    fs::write(dir.path().join("Cargo.toml"), "# needle\n").unwrap();
+    fs::write(dir.path().join("notes.txt"), "needle\n").unwrap();
+
+    let grep = |flags: &[&str]| {
+        let output = simple_grep()
+            .arg("-r")
+            .args(flags)
+            .arg("needle")
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        (
+            stdout(&output),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+
+    let main = dir.path().join("src/main.rs");
+    let cargo = dir.path().join("Cargo.toml");
+    assert_eq!(
+        grep(&["--type", "rust"]).0,
+        format!("{}:// needle\n", main.display())
+    );
+    assert_eq!(
+        grep(&["--type", "rust", "--type-add", "rust:*.toml"]).0,
+        format!(
+            "{}:# needle\n{}:// needle\n",
+            cargo.display(),
+            main.display()
+        )
+    );
+    assert_eq!(
+        grep(&["--type", "cobol"]).1,
+        "Application error: unknown file type 'cobol'\n"
+    );
+}
+
+#[test]
+fn glob_ignore_case_matches_extensions_in_any_case() {
+    let dir = fixture("mixed_case");
+
+    let grep = |flags: &[&str]| {
+        let output = simple_grep()
+            .args(["-r", "-c", "--type", "txt"])
+            .args(flags)
+            .arg("needle")
+            .arg(&dir)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    assert_eq!(
+        grep(&[]),
+        format!("{}:1\n", dir.join("lower.txt").display())
+    );
+    assert_eq!(
+        grep(&["--glob-ignore-case"]),
+        format!(
+            "{}:1\n{}:1\n{}:1\n",
+            dir.join("Title.Txt").display(),
+            dir.join("UPPER.TXT").display(),
+            dir.join("lower.txt").display()
+        )
+    );
+}
+
+#[test]
+fn reverse_file_order_searches_the_last_file_first() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+    fs::write(dir.path().join("c.txt"), "needle in c\n").unwrap();
+
+    let output = simple_grep()
+        .args(["-r", "--reverse-file-order", "--sort-lines", "needle"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}:needle in b\n{}:needle in c\n{}:needle in a\n",
+            dir.path().join("sub/b.txt").display(),
+            dir.path().join("c.txt").display(),
+            dir.path().join("a.txt").display()
+        )
+    );
+}
+
+#[test]
+fn recursive_search_prefixes_filenames() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+
+    let output = simple_grep()
+        .args(["-r", "-n", "needle"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        sorted_lines(&output),
+        [
+            format!("{}:1:needle in a", dir.path().join("a.txt").display()),
+            format!("{}:2:needle in b", dir.path().join("sub/b.txt").display()),
+        ]
+    );
+}
+
+#[test]
+fn files_starting_like_bzip2_are_searched_as_text() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::copy(fixture("bzh.txt"), dir.path().join("bzh.txt")).unwrap();
+
+    let output = simple_grep()
+        .args(["-r", "word"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(
+        stdout(&output),
+        format!("{}:BZh is a word\n", dir.path().join("bzh.txt").display())
+    );
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn files_starting_like_gzip_are_searched_as_they_are() {
+    let output = simple_grep()
+        .args(["--hex", "1f8b"])
+        .arg(fixture("gzip_magic.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert!(
+        stdout(&output).starts_with("0: [1f 8b]"),
+        "{}",
+        stdout(&output)
+    );
+}
+
+#[test]
+fn recursive_search_visits_entries_in_order_of_name() {
+    // Created out of order, so that the order `read_dir` lists them in on
+    // most file systems isn't that of their names.
+    let dir = tempfile::tempdir().unwrap();
+    for name in ["d.txt", "b/z.txt", "c.txt", "b/a.txt", "a.txt", "B.txt"] {
+        let path = dir.path().join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, "needle\n").unwrap();
+    }
+
+    let search = || {
+        simple_grep()
+            .current_dir(dir.path())
+            .args(["-r", "-c", "needle", "."])
+            .output()
+            .unwrap()
+    };
+
+    let output = search();
+    assert!(output.status.success());
+    let expected = ["B.txt", "a.txt", "b/a.txt", "b/z.txt", "c.txt", "d.txt"]
+        .iter()
+        .map(|name| format!("{}:1\n", Path::new(".").join(name).display()))
+        .collect::<String>();
+    assert_eq!(stdout(&output), expected);
+    assert_eq!(stdout(&search()), expected);
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_terminates_on_symlink_loop() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+    std::os::unix::fs::symlink(dir.path(), dir.path().join("sub/loop")).unwrap();
+    std::os::unix::fs::symlink(dir.path().join("a.txt"), dir.path().join("link.txt")).unwrap();
+
+    let output = simple_grep()
+        .args(["-r", "--follow", "needle"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        sorted_lines(&output),
+        [
+            format!("{}:needle in a", dir.path().join("a.txt").display()),
+            format!("{}:needle in a", dir.path().join("link.txt").display()),
+            format!("{}:needle in b", dir.path().join("sub/b.txt").display()),
+        ]
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("sub/loop: recursive directory loop"));
+}
+
+#[cfg(unix)]
+#[test]
+fn follow_searches_a_directory_reached_through_two_links_once() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+    let other = tempfile::tempdir().unwrap();
+    fs::write(other.path().join("c.txt"), "needle in c\n").unwrap();
+    std::os::unix::fs::symlink(other.path(), dir.path().join("x")).unwrap();
+    std::os::unix::fs::symlink(other.path(), dir.path().join("y")).unwrap();
+
+    let output = simple_grep()
+        .args(["-r", "--follow", "needle"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        sorted_lines(&output),
+        [
+            format!("{}:needle in a", dir.path().join("a.txt").display()),
+            format!("{}:needle in b", dir.path().join("sub/b.txt").display()),
+            format!("{}:needle in c", dir.path().join("x/c.txt").display()),
+        ]
+    );
+    // It's not a loop, so there's nothing to warn about.
+    assert!(output.stderr.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn recursion_skips_symlinks_by_default() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+    std::os::unix::fs::symlink(dir.path(), dir.path().join("sub/loop")).unwrap();
+    std::os::unix::fs::symlink(dir.path().join("a.txt"), dir.path().join("link.txt")).unwrap();
+
+    let output = simple_grep()
+        .args(["-r", "needle"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(sorted_lines(&output).len(), 2);
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn format_renders_custom_template() {
+    let path = fixture("languages.txt");
+    let output = simple_grep()
+        .args(["-i", "--format", "{path}|{lineno}|{count}|{text}", "rust"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let path = path.display();
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "\
+{path}|1|1|Rust is empowering everyone to build reliable and efficient software.
+{path}|5|1|RUST can be used for web development as well.
+{path}|6|2|Trust me, rUsT will be huge!
+"
+        )
+    );
+}
+
+#[test]
+fn format_rejects_unknown_placeholders() {
+    let output = simple_grep()
+        .args(["--format={path}:{column}", "rust"])
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Unknown placeholder '{column}'"));
+}
+
+#[test]
+fn swapped_arguments_are_warned_about() {
+    let output = simple_grep()
+        .arg(fixture("app.log"))
+        .arg("ERROR")
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the arguments may be swapped"), "{stderr}");
+
+    let output = simple_grep()
+        .arg("ERROR")
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn line_buffered_reads_standard_input() {
+    let mut child = simple_grep()
+        .args(["--line-buffered", "-n", "ERROR", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"INFO: up\nERROR: down\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "2:ERROR: down\n");
+}
+
+#[test]
+fn stdin_paths_searches_the_files_named_on_standard_input() {
+    let paths = format!(
+        "{}\n{}\n",
+        fixture("app.log").display(),
+        fixture("languages.txt").display()
+    );
+
+    for (args, input) in [
+        (&["--stdin-paths", "-c", "ERROR"][..], paths.clone()),
+        (
+            &["--stdin-paths", "-Z", "-c", "ERROR"],
+            paths.replace('\n', "\0"),
+        ),
+    ] {
+        let mut child = simple_grep()
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(input.as_bytes())
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+
+        assert!(output.status.success());
+        assert_eq!(
+            stdout(&output),
+            format!(
+                "{}:4\n{}:0\n",
+                fixture("app.log").display(),
+                fixture("languages.txt").display()
+            )
+        );
+    }
+}
+
+#[test]
+fn max_columns_truncates_on_char_boundaries() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("long.txt");
+    fs::write(&path, "short match\nmatch ééééé and then some\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--max-columns", "8", "match"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "short ma…\nmatch éé…\n");
+}
+
+#[test]
+fn passthru_prints_all_lines_and_marks_matches() {
+    let output = simple_grep()
+        .args(["--passthru", "--mark", "-n", "disk"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "\
+1-INFO: service started
+2:ERROR: >>disk<< full
+3-WARN: retrying
+4-ERROR: connection refused
+5:ERROR: >>disk<< full
+6-INFO: cleanup
+7:ERROR: >>disk<< full
+"
+    );
+}
+
+#[test]
+fn color_always_highlights_matches() {
+    let output = simple_grep()
+        .args(["--color=always", "-i", "rust"])
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(
+        stdout(&output)
+            .ends_with("T\x1b[1;31mrust\x1b[0m me, \x1b[1;31mrUsT\x1b[0m will be huge!\n")
+    );
+}
+
+#[test]
+fn escape_nonprinting_shows_control_characters() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("control.log");
+    fs::write(&path, "plain\n\x1b[31mERROR\x1b[0m:\tbell\x07\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--escape-nonprinting", "ERROR"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "^[[31mERROR^[[0m:\tbell^G\n");
+}
+
+#[test]
+fn dim_context_dims_the_text_around_matches() {
+    let output = simple_grep()
+        .args(["--color=always", "--dim-context", "-i", "rust"])
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(
+        stdout(&output)
+            .ends_with("\x1b[2mT\x1b[0mrust\x1b[2m me, \x1b[0mrUsT\x1b[2m will be huge!\x1b[0m\n")
+    );
+}
+
+#[test]
+fn summary_reports_lines_and_bytes_per_file_and_total() {
+    let log = fixture("app.log");
+    let languages = fixture("languages.txt");
+
+    let output = simple_grep()
+        .args(["--summary", "ERROR"])
+        .arg(&log)
+        .arg(&languages)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}: 7 lines, 128 bytes, 4 matching lines, 73 matching bytes\n\
+             {}: 7 lines, 342 bytes, 0 matching lines, 0 matching bytes\n\
+             total: 14 lines, 470 bytes, 4 matching lines, 73 matching bytes\n",
+            log.display(),
+            languages.display()
+        )
+    );
+}
+
+#[test]
+fn ratio_reports_selected_lines_per_file() {
+    let log = fixture("app.log");
+    let languages = fixture("languages.txt");
+
+    let output = simple_grep()
+        .args(["--ratio", "ERROR"])
+        .arg(&log)
+        .arg(&languages)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}: 4/7 (57.1%)\n{}: 0/7 (0.0%)\n",
+            log.display(),
+            languages.display()
+        )
+    );
+}
+
+#[test]
+fn ratio_counts_non_matching_lines_when_inverted() {
+    let log = fixture("app.log");
+
+    let output = simple_grep()
+        .args(["--ratio", "-v", "ERROR"])
+        .arg(&log)
+        .output()
+        .unwrap();
+
+    assert_eq!(stdout(&output), format!("{}: 3/7 (42.9%)\n", log.display()));
+}
+
+#[test]
+fn density_reports_matches_per_100_lines_and_longest_run() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("clustered.log");
+    // Lines 1 to 3, 10, 15 and 16 of 20 match.
+    let contents: String = (1..=20)
+        .map(|line| match line {
+            1..=3 | 10 | 15 | 16 => format!("ERROR {line}\n"),
+            _ => format!("INFO {line}\n"),
+        })
+        .collect();
+    fs::write(&path, contents).unwrap();
+
+    let output = simple_grep()
+        .args(["--density", "ERROR"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}: 30.0 matches per 100 lines, longest run 3\n",
+            path.display()
+        )
+    );
+}
+
+#[test]
+fn bottom_up_searches_from_the_last_line_keeping_line_numbers() {
+    let grep = |flags: &[&str]| {
+        let output = simple_grep()
+            .args(["--bottom-up", "-n"])
+            .args(flags)
+            .arg(fixture("app.log"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    assert_eq!(
+        grep(&["ERROR"]),
+        "7:ERROR: disk full\n5:ERROR: disk full\n4:ERROR: connection refused\n2:ERROR: disk full\n"
+    );
+    // Context follows the search order: the line below comes before.
+    assert_eq!(
+        grep(&["-B1", "INFO"]),
+        "7-ERROR: disk full\n6:INFO: cleanup\n--\n2-ERROR: disk full\n1:INFO: service started\n"
+    );
+}
+
+#[test]
+fn invert_match_selects_non_matching_lines() {
+    let output = simple_grep()
+        .args(["-v", "-n", "ERROR"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        stdout(&output),
+        "1:INFO: service started\n3:WARN: retrying\n6:INFO: cleanup\n"
+    );
+}
+
+#[test]
+fn dedupe_files_searches_each_file_once() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+    let a = dir.path().join("a.txt");
+
+    let output = simple_grep()
+        .args(["--dedupe-files", "-r", "needle"])
+        .arg(&a)
+        .arg(dir.path().join("sub/../a.txt"))
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output).lines().collect::<Vec<_>>(),
+        [
+            format!("{}:needle in a", a.display()),
+            format!("{}:needle in b", dir.path().join("sub/b.txt").display()),
+        ]
+    );
+}
+
+#[test]
+fn skip_duplicate_content_searches_identical_files_once() {
+    let dir = fixture("duplicates");
+
+    let output = simple_grep()
+        .args(["--skip-duplicate-content", "-r", "-c", "needle"])
+        .arg(&dir)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}:1\n{}:1\n",
+            dir.join("copy_a.txt").display(),
+            dir.join("other.txt").display()
+        )
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!(
+            "simple_grep: {}: skipped, same content as {}\n",
+            dir.join("copy_b.txt").display(),
+            dir.join("copy_a.txt").display()
+        )
+    );
+}
+
+#[test]
+fn report_empty_notes_empty_files() {
+    let empty = fixture("empty.txt");
+    let log = fixture("app.log");
+
+    let output = simple_grep()
+        .args(["--report-empty", "-c", "-v", "ERROR"])
+        .arg(&empty)
+        .arg(&log)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!("{}:0\n{}:3\n", empty.display(), log.display())
+    );
+    assert_eq!(
+        String::from_utf8_lossy(&output.stderr),
+        format!("simple_grep: {}: empty file\n", empty.display())
+    );
+}
+
+#[test]
+fn same_file_is_searched_twice_without_dedupe() {
+    let path = fixture("languages.txt");
+
+    let output = simple_grep()
+        .arg("Go")
+        .arg(&path)
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert_eq!(stdout(&output).lines().count(), 2);
+}
+
+#[test]
+fn csv_quotes_fields_with_commas_and_quotes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("quotes.txt");
+    fs::write(&path, "say \"hi\", then leave\nplain hi\nbye\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--csv-header", "hi"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let path = path.display();
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "\
+path,line_number,text
+{path},1,\"say \"\"hi\"\", then leave\"
+{path},2,plain hi
+"
+        )
+    );
+}
+
+#[test]
+fn context_separates_groups() {
+    let output = simple_grep()
+        .args(["-n", "-C1", "WARN"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        stdout(&output),
+        "2-ERROR: disk full\n3:WARN: retrying\n4-ERROR: connection refused\n"
+    );
+
+    let output = simple_grep()
+        .args(["-n", "-A", "1", "-B0", "INFO"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        stdout(&output),
+        "1:INFO: service started\n2-ERROR: disk full\n--\n6:INFO: cleanup\n7-ERROR: disk full\n"
+    );
+}
+
+#[test]
+fn huge_before_context_is_not_allocated_up_front() {
+    let output = simple_grep()
+        .args(["-n", "-B", "4000000000", "WARN"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "1-INFO: service started\n2-ERROR: disk full\n3:WARN: retrying\n"
+    );
+}
+
+#[test]
+fn context_surrounds_non_matching_lines_when_inverted() {
+    let output = simple_grep()
+        .args(["-v", "-C1", "-n", "ERROR"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "\
+1:INFO: service started
+2-ERROR: disk full
+3:WARN: retrying
+4-ERROR: connection refused
+5-ERROR: disk full
+6:INFO: cleanup
+7-ERROR: disk full
+"
+    );
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("gaps.txt");
+    fs::write(&path, "a x\nb x\nc x\nd\ne x\nf x\ng x\nh\n").unwrap();
+
+    let output = simple_grep()
+        .args(["-v", "-C1", "-n", "x"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert_eq!(stdout(&output), "3-c x\n4:d\n5-e x\n--\n7-g x\n8:h\n");
+}
+
+#[test]
+fn fuzzy_tolerates_typos() {
+    let output = simple_grep()
+        .args(["--fuzzy", "--max-distance", "2", "-o", "powerfull"])
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "powerful\n");
+}
+
+#[test]
+fn max_matches_per_line_caps_only_matching_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("many.txt");
+    fs::write(&path, "ab ab ab ab ab\nno match\nab\n").unwrap();
+
+    let output = simple_grep()
+        .args(["-o", "-b", "--max-matches-per-line", "2", "ab"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "0:ab\n3:ab\n24:ab\n");
+}
+
+#[test]
+fn count_total_sums_selected_lines_across_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths: Vec<_> = [
+        ("one.txt", "ERROR a\nok\n"),
+        ("two.txt", "ok\n"),
+        ("three.txt", "ERROR b\nERROR c\n"),
+    ]
+    .iter()
+    .map(|(name, contents)| {
+        let path = dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    })
+    .collect();
+
+    let output = simple_grep()
+        .args(["-c", "--count-total", "ERROR"])
+        .args(&paths)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}:1\n{}:0\n{}:2\ntotal:3\n",
+            paths[0].display(),
+            paths[1].display(),
+            paths[2].display()
+        )
+    );
+
+    let output = simple_grep()
+        .args(["--count-total", "ERROR"])
+        .args(&paths)
+        .output()
+        .unwrap();
+
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{0}:ERROR a\n{1}:ERROR b\n{1}:ERROR c\ntotal:3\n",
+            paths[0].display(),
+            paths[2].display()
+        )
+    );
+}
+
+#[test]
+fn only_in_prints_matching_lines_missing_from_the_other_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let new = dir.path().join("new.log");
+    let old = dir.path().join("old.log");
+    fs::write(
+        &new,
+        "ERROR: disk full\nINFO: ok\nERROR: timeout\nerror: DISK FULL\n",
+    )
+    .unwrap();
+    fs::write(&old, "error: disk full\nERROR: refused\nINFO: timeout\n").unwrap();
+
+    let output = simple_grep()
+        .args(["-n", "--only-in"])
+        .arg(&new)
+        .arg("ERROR")
+        .arg(&old)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "1:ERROR: disk full\n3:ERROR: timeout\n");
+
+    let output = simple_grep()
+        .args(["-i", "--symmetric", "--only-in"])
+        .arg(&new)
+        .arg("ERROR")
+        .arg(&old)
+        .output()
+        .unwrap();
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{0}:ERROR: timeout\n{1}:ERROR: refused\n",
+            new.display(),
+            old.display()
+        )
+    );
+}
+
+#[test]
+fn skip_drops_the_first_matches_of_each_file() {
+    let log = fixture("app.log");
+
+    let output = simple_grep()
+        .args(["-n", "--skip", "2", "ERROR"])
+        .arg(&log)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "5:ERROR: disk full\n7:ERROR: disk full\n");
+
+    let output = simple_grep()
+        .args(["-c", "--skip", "3", "ERROR"])
+        .arg(&log)
+        .arg(&log)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), format!("{0}:1\n{0}:1\n", log.display()));
+}
+
+#[test]
+fn max_total_stops_after_n_matches_across_files() {
+    let dir = tempfile::tempdir().unwrap();
+    let paths: Vec<_> = [
+        ("one.txt", "ERROR a\nok\nERROR b\n"),
+        ("two.txt", "ERROR c\nERROR d\nERROR e\n"),
+        ("three.txt", "ERROR f\n"),
+    ]
+    .iter()
+    .map(|(name, contents)| {
+        let path = dir.path().join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    })
+    .collect();
+
+    let output = simple_grep()
+        .args(["--max-total", "3", "-j", "4", "--count-total", "ERROR"])
+        .args(&paths)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{0}:ERROR a\n{0}:ERROR b\n{1}:ERROR c\ntotal:3\n",
+            paths[0].display(),
+            paths[1].display()
+        )
+    );
+}
+
+#[test]
+fn sample_with_seed_is_reproducible() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("many.txt");
+    let contents: String = (1..=100)
+        .map(|n| format!("match {n}\nother {n}\n"))
+        .collect();
+    fs::write(&path, contents).unwrap();
+
+    let sample = |seed: &str| {
+        let output = simple_grep()
+            .args(["--sample", "5", "--seed", seed, "match"])
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    let first = sample("42");
+    assert_eq!(first, sample("42"));
+    assert_ne!(first, sample("7"));
+
+    let numbers: Vec<usize> = first
+        .lines()
+        .map(|line| line.strip_prefix("match ").unwrap().parse().unwrap())
+        .collect();
+    assert_eq!(numbers.len(), 5);
+    assert!(numbers.windows(2).all(|pair| pair[0] < pair[1]));
+}
+
+#[test]
+fn sample_larger_than_the_matches_prints_them_all() {
+    let output = simple_grep()
+        .args(["--sample", "4000000000", "disk"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    let expected = simple_grep()
+        .arg("disk")
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(!output.stdout.is_empty());
+    assert_eq!(stdout(&output), stdout(&expected));
+}
+
+#[test]
+fn from_to_searches_each_range() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ranges.txt");
+    fs::write(
+        &path,
+        "x before\nBEGIN x\nx one\nEND x\nx between\nBEGIN\nx two\nEND\nx after\n",
+    )
+    .unwrap();
+
+    let output = simple_grep()
+        .args(["-n", "--from", "BEGIN", "--to", "END", "x"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "2:BEGIN x\n3:x one\n4:END x\n7:x two\n");
+}
+
+fn json_output(flag: &str, query: &str) -> String {
+    let output = simple_grep()
+        .args([flag, query])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    stdout(&output)
+}
+
+#[test]
+fn json_lines_writes_one_object_per_match() {
+    let path = fixture("app.log").display().to_string();
+    let path = path.replace('\\', "\\\\");
+
+    assert_eq!(json_output("--json", "no such line"), "");
+    assert_eq!(
+        json_output("--json-lines", "refused"),
+        format!(
+            "{{\"path\":\"{path}\",\"line_number\":4,\"text\":\"ERROR: connection refused\"}}\n"
+        )
+    );
+    assert_eq!(json_output("--json", "ERROR").lines().count(), 4);
+}
+
+#[test]
+fn json_includes_context_lines_of_each_match() {
+    let path = fixture("app.log").display().to_string();
+    let path = path.replace('\\', "\\\\");
+    let output = simple_grep()
+        .args(["--json", "-C1", "disk"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    let context = |number, text| format!("{{\"line_number\":{number},\"text\":\"{text}\"}}");
+    let object = |number, before: &[String], after: &[String]| {
+        format!(
+            "{{\"path\":\"{path}\",\"line_number\":{number},\"text\":\"ERROR: disk full\",\
+             \"before\":[{}],\"after\":[{}]}}\n",
+            before.join(","),
+            after.join(",")
+        )
+    };
+
+    // Line 6 follows the match on line 5, so it isn't before line 7 too.
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        [
+            object(
+                2,
+                &[context(1, "INFO: service started")],
+                &[context(3, "WARN: retrying")]
+            ),
+            object(
+                5,
+                &[context(4, "ERROR: connection refused")],
+                &[context(6, "INFO: cleanup")]
+            ),
+            object(7, &[], &[]),
+        ]
+        .concat()
+    );
+}
+
+#[test]
+fn word_start_matches_word_prefixes_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("words.txt");
+    fs::write(&path, "a cat\ncategory\nconcatenate\nbobcat\n").unwrap();
+
+    let grep = |flags: &[&str]| {
+        let output = simple_grep()
+            .args(flags)
+            .arg("cat")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    assert_eq!(grep(&[]), "a cat\ncategory\nconcatenate\nbobcat\n");
+    assert_eq!(grep(&["--word-start"]), "a cat\ncategory\n");
+    assert_eq!(grep(&["-w"]), "a cat\n");
+    assert_eq!(grep(&["--word-start", "-o", "-b"]), "2:cat\n6:cat\n");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn min_match_len_drops_short_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("ids.txt");
+    fs::write(&path, "id 7\nid 12345\nids 42 and 1000\nno id\n").unwrap();
+
+    let grep = |flags: &[&str]| {
+        let output = simple_grep()
+            .args(flags)
+            .args(["-E", "-n", "[0-9]+"])
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    assert_eq!(grep(&[]), "1:id 7\n2:id 12345\n3:ids 42 and 1000\n");
+    assert_eq!(
+        grep(&["--min-match-len", "3"]),
+        "2:id 12345\n3:ids 42 and 1000\n"
+    );
+    assert_eq!(grep(&["--min-match-len", "3", "-o"]), "2:12345\n3:1000\n");
+    assert_eq!(grep(&["--min-match-len", "6"]), "");
+}
+
+#[test]
+fn squeeze_whitespace_matches_despite_spacing() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("spaced.log");
+    fs::write(&path, "ERROR:   disk \t full\nERROR: diskfull\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--squeeze-whitespace", "--mark", "disk full"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "ERROR:   >>disk \t full<<\n");
+
+    let output = simple_grep().arg("disk full").arg(&path).output().unwrap();
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn case_variants_matches_the_title_case_of_the_query() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("greetings.txt");
+    fs::write(
+        &path,
+        "say Hello World
+say hElLo wOrLd
+",
+    )
+    .unwrap();
+
+    let output = simple_grep()
+        .args(["--case-variants", "--mark", "hello world"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "say >>Hello World<<\n");
+
+    let output = simple_grep()
+        .arg("hello world")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn json_reports_read_errors_as_json() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing.txt");
+
+    let output = simple_grep()
+        .args(["--json", "ERROR"])
+        .arg(&missing)
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let prefix = format!(
+        r#"{{"error":"failed to read","path":"{}","cause":""#,
+        missing.display()
+    );
+    assert!(stderr.starts_with(&prefix), "{stderr}");
+    assert!(stderr.ends_with("\"}\n"), "{stderr}");
+    assert_eq!(stderr.lines().count(), 1);
+}
+
+#[test]
+fn json_array_is_well_formed() {
+    let path = fixture("app.log").display().to_string();
+    let path = path.replace('\\', "\\\\");
+
+    assert_eq!(json_output("--json-array", "no such line"), "[]\n");
+    assert_eq!(
+        json_output("--json-array", "refused"),
+        format!(
+            "[\n{{\"path\":\"{path}\",\"line_number\":4,\"text\":\"ERROR: connection refused\"}}\n]\n"
+        )
+    );
+
+    let many = json_output("--json-array", "ERROR");
+    let lines: Vec<&str> = many.lines().collect();
+    assert_eq!(lines.len(), 6);
+    assert_eq!((lines[0], lines[5]), ("[", "]"));
+    assert!(lines[1..4].iter().all(|line| line.ends_with("},")));
+    assert!(lines[4].ends_with('}'));
+}
+
+#[test]
+fn json_array_only_holds_json() {
+    let grep = |flags: &[&str]| {
+        simple_grep()
+            .arg("--json-array")
+            .args(flags)
+            .arg("ERROR")
+            .arg(fixture("app.log"))
+            .output()
+            .unwrap()
+    };
+
+    for flags in [&["-C1"][..], &["--sort-lines"], &["--max-total", "2"]] {
+        let output = grep(flags);
+        assert!(output.status.success(), "{flags:?}");
+        let array: serde_json::Value = serde_json::from_str(&stdout(&output)).unwrap();
+        assert!(array.is_array(), "{flags:?}");
+    }
+
+    for flag in ["-c", "--summary", "--ratio", "--group-count"] {
+        let output = grep(&[flag]);
+        assert!(!output.status.success(), "{flag}");
+        assert!(output.stdout.is_empty(), "{flag}");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("--json-array can't be combined"),
+            "{stderr}"
+        );
+    }
+}
+
+#[test]
+fn version_lists_features() {
+    let output = simple_grep().arg("--version").output().unwrap();
+
+    assert!(output.status.success());
+    let version = stdout(&output);
+    assert!(version.starts_with(&format!("simple_grep {}\n", env!("CARGO_PKG_VERSION"))));
+
+    let gzip = if cfg!(feature = "gzip") {
+        "+gzip"
+    } else {
+        "-gzip"
+    };
+    assert!(version.contains(gzip));
+}
+
+#[test]
+fn help_lists_options() {
+    let output = simple_grep().arg("--help").output().unwrap();
+
+    assert!(output.status.success());
+    let help = stdout(&output);
+    assert!(help.starts_with("Usage: simple_grep"));
+
+    for option in [
+        "-i, --ignore-case",
+        "-C, --context N",
+        "--color[=WHEN]",
+        "--json-array",
+    ] {
+        assert!(help.contains(option), "{option}");
+    }
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_only_matching_terminates_on_empty_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("empty.txt");
+    fs::write(&path, "baaac\nxyz\naa\n").unwrap();
+
+    let output = simple_grep()
+        .args(["-o", "-n", "-E", "a*"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "1:aaa\n3:aa\n");
+
+    let output = simple_grep()
+        .args(["-c", "-E", "a*"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_timeout_skips_slow_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("slow.txt");
+    let slow = "ab ".repeat(2_000_000);
+    fs::write(&path, format!("{slow}\nERROR: fast\n")).unwrap();
+
+    let output = simple_grep()
+        .args(["-E", "-n", "--regex-timeout", "1", r"(\w+\s?)*x|ERROR"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "2:ERROR: fast\n");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(":1: matching took longer than 1ms, line skipped"),
+        "{stderr}"
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_rejects_invalid_patterns() {
+    let output = simple_grep()
+        .args(["-E", "(unclosed"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Invalid regular expression"));
+}
+
+#[test]
+fn merge_adjacent_coalesces_touching_matches() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("adjacent.txt");
+    fs::write(&path, "ababx ab\n").unwrap();
+
+    let run = |args: &[&str]| {
+        let output = simple_grep().args(args).arg(&path).output().unwrap();
+        stdout(&output)
+    };
+
+    assert_eq!(run(&["-o", "ab"]), "ab\nab\nab\n");
+    assert_eq!(run(&["-o", "--merge-adjacent", "ab"]), "abab\nab\n");
+    assert_eq!(run(&["--mark", "ab"]), ">>ab<<>>ab<<x >>ab<<\n");
+    assert_eq!(
+        run(&["--mark", "--merge-adjacent", "ab"]),
+        ">>abab<<x >>ab<<\n"
+    );
+}
+
+#[test]
+fn hex_reports_byte_offsets() {
+    let output = simple_grep()
+        .args(["--hex", "DEADBEEF"])
+        .arg(fixture("bytes.bin"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "4: 00 01 02 03 [de ad be ef] 04 05 10 10\n16: 10 10 10 10 [de ad be ef]\n"
+    );
+
+    let output = simple_grep()
+        .args(["-c", "--hex", "0x10 10"])
+        .arg(fixture("bytes.bin"))
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "3\n");
+}
+
+#[test]
+fn strict_utf8_reports_the_offset_of_invalid_bytes() {
+    let output = simple_grep()
+        .args(["--strict-utf8", "valid"])
+        .arg(fixture("invalid_utf8.txt"))
+        .output()
+        .unwrap();
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("invalid_utf8.txt: invalid UTF-8 at byte offset 15"),
+        "{stderr}"
+    );
+
+    // Files found recursively are skipped unless the check is strict.
+    let dir = tempfile::tempdir().unwrap();
+    fs::copy(fixture("invalid_utf8.txt"), dir.path().join("bad.txt")).unwrap();
+    fs::write(dir.path().join("good.txt"), "valid\n").unwrap();
+
+    let search = |args: &[&str]| {
+        simple_grep()
+            .args(args)
+            .args(["-r", "valid"])
+            .arg(dir.path())
+            .output()
+            .unwrap()
+    };
+    assert!(search(&[]).status.success());
+    assert!(!search(&["--strict-utf8"]).status.success());
+}
+
+#[test]
+fn find_non_ascii_reports_byte_positions() {
+    let output = simple_grep()
+        .arg("--find-non-ascii")
+        .arg(fixture("mixed.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "2: 4:0xc3 5:0xa9\n4: 5:0xff\n");
+
+    let output = simple_grep()
+        .args(["-c", "--find-non-ascii"])
+        .arg(fixture("mixed.txt"))
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "2\n");
+}
+
+#[test]
+fn hex_rejects_invalid_bytes() {
+    for hex in ["", "ABC", "ZZ"] {
+        let output = simple_grep()
+            .args(["--hex", hex])
+            .arg(fixture("bytes.bin"))
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success(), "{hex}");
+    }
+}
+
+#[test]
+fn threaded_output_matches_single_threaded() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+    for n in 0..8 {
+        let contents: String = (0..50 + n * 10)
+            .map(|line| {
+                format!(
+                    "{} {line}\n",
+                    if line % (n + 2) == 0 { "needle" } else { "hay" }
+                )
+            })
+            .collect();
+        fs::write(dir.path().join(format!("file{n}.txt")), contents).unwrap();
+    }
+
+    let search = |threads: &str| {
+        let output = simple_grep()
+            .args(["-r", "-n", "-j", threads, "needle"])
+            .arg(dir.path())
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    let single = search("1");
+    assert!(single.lines().count() > 100);
+    assert_eq!(search("4"), single);
+    assert_eq!(search("16"), single);
+}
+
+#[test]
+fn parallel_within_file_matches_sequential_search() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("huge.log");
+    // About 500 KiB, enough to be split into parts.
+    let contents: String = (1..=20_000)
+        .map(|line| {
+            let level = if line % 7 == 0 { "ERROR" } else { "INFO" };
+            format!("{level}: request {line} handled\n")
+        })
+        .collect();
+    fs::write(&path, contents).unwrap();
+
+    let search = |args: &[&str]| {
+        let output = simple_grep()
+            .args(["-n", "-b"])
+            .args(args)
+            .arg("ERROR")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    let sequential = search(&[]);
+    let parallel = search(&["-j", "4", "--parallel-within-file"]);
+
+    assert_eq!(parallel, sequential);
+    assert_eq!(parallel.lines().count(), 20_000 / 7);
+    let last = parallel.lines().last().unwrap();
+    assert!(last.starts_with("19999:"), "{last}");
+    assert!(last.ends_with(":ERROR: request 19999 handled"), "{last}");
+}
+
+#[test]
+fn file_all_requires_every_pattern_somewhere_in_the_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let both = dir.path().join("both.conf");
+    let only_port = dir.path().join("only_port.conf");
+    let neither = dir.path().join("neither.conf");
+    fs::write(&both, "port = 80\nhost = example.org\n").unwrap();
+    fs::write(&only_port, "port = 443\n").unwrap();
+    fs::write(&neither, "debug = true\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--file-all", "-e", "port", "-e", "host"])
+        .arg(&both)
+        .arg(&only_port)
+        .arg(&neither)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!("{0}:port = 80\n{0}:host = example.org\n", both.display())
+    );
+
+    let output = simple_grep()
+        .args(["--file-all", "-e", "port", "-e", "host"])
+        .arg(&only_port)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn slurp_matches_queries_spanning_line_breaks() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("poem.txt");
+    fs::write(&path, "roses are red\nviolets are blue\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--slurp", "--mark", "red\nviolets"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}:roses are >>red\\nviolets<< are blue\\n\n",
+            path.display()
+        )
+    );
+
+    // Line by line, the query never matches.
+    let output = simple_grep()
+        .arg("red\nviolets")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "");
+}
+
+#[test]
+fn print_whole_file_dumps_matching_files_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let matching = dir.path().join("matching.conf");
+    let other = dir.path().join("other.conf");
+    fs::write(&matching, "port = 80\nhost = example.org\ndebug = true\n").unwrap();
+    fs::write(&other, "port = 443\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--print-whole-file", "--mark", "host"])
+        .arg(&matching)
+        .arg(&other)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{0}-port = 80\n{0}:>>host<< = example.org\n{0}-debug = true\n",
+            matching.display()
+        )
+    );
+
+    let output = simple_grep()
+        .args(["--print-whole-file", "host"])
+        .arg(&other)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "");
+}
+
+#[cfg(feature = "normalize")]
+#[test]
+fn normalize_matches_composed_and_decomposed_forms() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("cafe.txt");
+    // "café" decomposed: "e" followed by a combining acute accent.
+    fs::write(&path, "cafe\u{301} au lait\ntea\n").unwrap();
+    let composed = "caf\u{e9}";
+
+    let search = |args: &[&str]| {
+        let output = simple_grep().args(args).arg(&path).output().unwrap();
+        stdout(&output)
+    };
+
+    assert_eq!(search(&[composed]), "");
+    assert_eq!(
+        search(&["--normalize", "NFC", composed]),
+        "caf\u{e9} au lait\n"
+    );
+    assert_eq!(
+        search(&["--normalize", "nfd", composed]),
+        "cafe\u{301} au lait\n"
+    );
+    // Streamed byte offsets are in the file, before the first line got
+    // shorter.
+    assert_eq!(
+        search(&["--line-buffered", "-b", "--normalize", "NFC", "tea"]),
+        "15:tea\n"
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn multiline_matches_span_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("multiline.txt");
+    fs::write(&path, "alpha\nbegin one\ntwo end\nomega\n").unwrap();
+
+    let search = |args: &[&str]| {
+        let output = simple_grep().args(args).arg(&path).output().unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    assert_eq!(search(&["-n", "-U", r"one\ntwo"]), "2:one\ntwo\n");
+    assert_eq!(search(&["-n", "-U", "begin.*end"]), "");
+    assert_eq!(
+        search(&["-n", "-b", "--multiline-dotall", "begin.*end"]),
+        "2:6:begin one\ntwo end\n"
+    );
+    assert_eq!(search(&["-U", "^o.*$"]), "omega\n");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn flatten_matches_prints_each_multiline_match_on_one_line() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("multiline.txt");
+    fs::write(&path, "begin one\ntwo end\nbegin three\nfour end\n").unwrap();
+
+    let output = simple_grep()
+        .args([
+            "-n",
+            "--flatten-matches",
+            "--multiline-dotall",
+            "begin.*?end",
+        ])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "1:begin one\\ntwo end\n3:begin three\\nfour end\n"
+    );
+}
+
+#[test]
+fn output_encoding_writes_utf16le_with_bom() {
+    let dir = tempfile::tempdir().unwrap();
+    let output_path = dir.path().join("out.txt");
+
+    let status = simple_grep()
+        .args(["--output-encoding", "utf-16le", "refused"])
+        .arg(fixture("app.log"))
+        .stdout(fs::File::create(&output_path).unwrap())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let bytes = fs::read(&output_path).unwrap();
+    let mut expected = vec![0xff, 0xfe];
+    for unit in "ERROR: connection refused\n".encode_utf16() {
+        expected.extend_from_slice(&unit.to_le_bytes());
+    }
+    assert_eq!(bytes, expected);
+}
+
+#[test]
+fn files_with_counts_lists_matching_files_in_order() {
+    let log = fixture("app.log");
+    let languages = fixture("languages.txt");
+
+    let output = simple_grep()
+        .args(["--files-with-counts", "ERROR"])
+        .arg(&log)
+        .arg(&languages)
+        .arg(&log)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!("{0} (4)\n{0} (4)\n", log.display())
+    );
+
+    let output = simple_grep()
+        .args(["--files-with-counts", "-i", "rust"])
+        .arg(&languages)
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), format!("{} (3)\n", languages.display()));
+}
+
+#[test]
+fn exclude_matching_drops_lines_matching_the_secondary_pattern() {
+    let output = simple_grep()
+        .args(["-n", "--exclude-matching", "disk", "ERROR"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "4:ERROR: connection refused\n");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn exclude_matching_is_a_regex_with_regex() {
+    let output = simple_grep()
+        .args(["-E", "--exclude-matching", "^(INFO|ERROR): d", "^[A-Z]+:"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "INFO: service started\nWARN: retrying\nERROR: connection refused\nINFO: cleanup\n"
+    );
+}
+
+#[test]
+fn also_matching_requires_both_patterns() {
+    let output = simple_grep()
+        .args(["-n", "--also-matching", "full", "ERROR"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "2:ERROR: disk full\n5:ERROR: disk full\n7:ERROR: disk full\n"
+    );
+
+    let output = simple_grep()
+        .args(["-i", "--also-matching", "REFUSED", "error"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert_eq!(stdout(&output), "ERROR: connection refused\n");
+}
+
+#[test]
+fn summary_json_reports_files_matches_and_time_on_stderr() {
+    let output = simple_grep()
+        .args(["--summary-json", "--json", "ERROR"])
+        .arg(fixture("app.log"))
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).lines().count(), 4);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let elapsed = stderr
+        .strip_prefix(r#"{"files_scanned":2,"files_matched":1,"total_matches":4,"elapsed_ms":"#)
+        .and_then(|rest| rest.strip_suffix("}\n"))
+        .unwrap_or_else(|| panic!("{stderr}"));
+    assert!(elapsed.parse::<u64>().is_ok(), "{stderr}");
+}
+
+#[test]
+fn measure_throughput_reports_on_stderr() {
+    let output = simple_grep()
+        .args(["--measure-throughput", "ERROR"])
+        .arg(fixture("app.log"))
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).lines().count(), 8);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let report = stderr.strip_prefix("throughput: ").unwrap();
+    let (rate, rest) = report.split_once(" MB/s (").unwrap();
+    assert!(rate.parse::<f64>().is_ok(), "{stderr}");
+    let (seconds, rest) = rest
+        .strip_prefix("256 bytes in ")
+        .unwrap()
+        .split_once('s')
+        .unwrap();
+    assert!(seconds.parse::<f64>().is_ok(), "{stderr}");
+    assert_eq!(rest, ")\n");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn whole_file_match_sets_the_exit_status() {
+    let dir = tempfile::tempdir().unwrap();
+    let valid = dir.path().join("valid.conf");
+    let invalid = dir.path().join("invalid.conf");
+    fs::write(&valid, "name=grep\nversion=1\n").unwrap();
+    fs::write(&invalid, "name=grep\nbroken line\n").unwrap();
+    let pattern = r"\A(\w+=\w+\n)*\z";
+
+    let output = simple_grep()
+        .args(["--whole-file-match", pattern])
+        .arg(&valid)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert!(output.stdout.is_empty());
+
+    let output = simple_grep()
+        .args(["--whole-file-match", pattern])
+        .arg(&invalid)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+
+    let output = simple_grep()
+        .args(["--whole-file-match", "-c", pattern])
+        .arg(&valid)
+        .arg(&invalid)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        format!("{}:1\n{}:0\n", valid.display(), invalid.display())
+    );
+}
+
+#[test]
+fn regexp_selects_lines_matching_any_pattern() {
+    let output = simple_grep()
+        .args(["-n", "-e", "WARN", "-e", "refused"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "3:WARN: retrying\n4:ERROR: connection refused\n"
+    );
+
+    // Overlapping matches of different patterns are printed once.
+    let output = simple_grep()
+        .args(["-o", "-e", "disk", "-e", "sk f", "-e", "ERROR"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output).lines().nth(1), Some("disk f"));
+}
+
+#[test]
+fn pattern_file_coverage_counts_files_matching_each_pattern() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.log"), "ERROR: disk full\nWARN: slow\n").unwrap();
+    fs::write(dir.path().join("b.log"), "ERROR: timeout\nERROR: timeout\n").unwrap();
+    fs::write(dir.path().join("c.log"), "INFO: ok\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--pattern-file-coverage", "-r", "-e", "ERROR", "-e", "WARN"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "ERROR: 2/3\nWARN: 1/3\n");
+}
+
+#[test]
+fn cooccurrence_counts_lines_matching_each_pair_of_patterns() {
+    let output = simple_grep()
+        .args([
+            "--cooccurrence",
+            "-e",
+            "ERROR",
+            "-e",
+            "disk",
+            "-e",
+            "refused",
+        ])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "ERROR & disk: 3\nERROR & refused: 1\ndisk & refused: 0\n"
+    );
+}
+
+#[test]
+fn count_by_pattern_counts_lines_matching_each_pattern() {
+    let log = fixture("app.log");
+
+    let output = simple_grep()
+        .args([
+            "--count-by-pattern",
+            "-e",
+            "ERROR",
+            "-e",
+            "disk",
+            "-e",
+            "WARN",
+        ])
+        .arg(&log)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "ERROR: 4, disk: 3, WARN: 1\n");
+
+    let output = simple_grep()
+        .args(["--count-by-pattern", "-i", "-e", "error", "-e", "FULL"])
+        .arg(&log)
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}:error: 4, FULL: 3\n{}:error: 0, FULL: 0\n",
+            log.display(),
+            fixture("languages.txt").display()
+        )
+    );
+}
+
+#[test]
+fn label_patterns_lists_the_patterns_each_line_matches() {
+    let output = simple_grep()
+        .args([
+            "--label-patterns",
+            "-e",
+            "err=ERROR",
+            "-e",
+            "disk",
+            "-e",
+            "WARN",
+        ])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "[err,disk] ERROR: disk full\n\
+         [WARN] WARN: retrying\n\
+         [err] ERROR: connection refused\n\
+         [err,disk] ERROR: disk full\n\
+         [err,disk] ERROR: disk full\n"
+    );
+}
+
+#[test]
+fn char_count_counts_characters_not_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("accents.txt");
+    let line = "café déjà vu";
+    fs::write(&path, format!("plain match\n{line} match\n")).unwrap();
+
+    let output = simple_grep()
+        .args(["--char-count", "match"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "(11 chars) plain match\n(18 chars) café déjà vu match\n"
+    );
+    assert_eq!("café déjà vu match".len(), 21);
+}
+
+#[test]
+fn percentile_reports_the_length_of_the_nearest_rank() {
+    let dir = tempfile::tempdir().unwrap();
+    let long = dir.path().join("long.txt");
+    let short = dir.path().join("short.txt");
+    // Lines of 1 to 20 characters, and 5 of 1 character.
+    let lines: String = (1..=20).map(|length| "x".repeat(length) + "\n").collect();
+    fs::write(&long, lines).unwrap();
+    fs::write(&short, "x\n".repeat(5)).unwrap();
+
+    let grep = |percentile: &str| {
+        let output = simple_grep()
+            .args(["--percentile", percentile, "x"])
+            .arg(&long)
+            .arg(&short)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    assert_eq!(
+        grep("95"),
+        format!(
+            "{}: p95 line length 19\n{}: p95 line length 1\ntotal: p95 line length 19\n",
+            long.display(),
+            short.display()
+        )
+    );
+    assert_eq!(
+        grep("50"),
+        format!(
+            "{}: p50 line length 10\n{}: p50 line length 1\ntotal: p50 line length 8\n",
+            long.display(),
+            short.display()
+        )
+    );
+}
+
+#[test]
+fn columns_count_characters_not_bytes() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("accents.txt");
+    fs::write(&path, "plain match\ncafé match, match\n").unwrap();
+
+    let grep = |flags: &[&str]| {
+        let output = simple_grep()
+            .args(flags)
+            .arg("match")
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    // `é` takes two bytes but is a single column.
+    assert_eq!(
+        grep(&["--columns"]),
+        "1:7-11:plain match\n2:6-10:café match, match\n"
+    );
+    assert_eq!(
+        grep(&["--columns", "-o"]),
+        "1:7-11:match\n2:6-10:match\n2:13-17:match\n"
+    );
+}
+
+#[test]
+fn report_unmatched_lists_patterns_without_matches() {
+    let output = simple_grep()
+        .args(["--report-unmatched", "-e", "ERROR", "-e", "EROR"])
+        .arg(fixture("app.log"))
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).lines().count(), 4);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(stderr, "simple_grep: no lines matched pattern: EROR\n");
+}
+
+#[test]
+fn longest_prints_the_longest_matching_line_of_each_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("lengths.txt");
+    fs::write(
+        &path,
+        "ERROR short\nERROR: much longer\nok, but the longest line\nERROR: same length\néRROR\n",
+    )
+    .unwrap();
+
+    let output = simple_grep()
+        .args(["--longest", "ERROR"])
+        .arg(&path)
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}:2:ERROR: much longer\n{}:4:ERROR: connection refused\n",
+            path.display(),
+            fixture("app.log").display()
+        )
+    );
+}
+
+#[test]
+fn sort_lines_keeps_line_numbers_attached() {
+    let output = simple_grep()
+        .args(["-n", "--sort-lines", ":"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "4:ERROR: connection refused\n2:ERROR: disk full\n5:ERROR: disk full\n\
+         7:ERROR: disk full\n6:INFO: cleanup\n1:INFO: service started\n3:WARN: retrying\n"
+    );
+
+    let output = simple_grep()
+        .args(["-n", "--sort-lines", "--reverse", "ERROR"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert_eq!(
+        stdout(&output),
+        "2:ERROR: disk full\n5:ERROR: disk full\n7:ERROR: disk full\n4:ERROR: connection refused\n"
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn strip_bom_lets_anchored_patterns_match_the_first_line() {
+    let bom = fixture("bom.txt");
+
+    let output = simple_grep()
+        .args(["-E", "^name"])
+        .arg(&bom)
+        .output()
+        .unwrap();
+    assert!(output.stdout.is_empty());
+
+    let output = simple_grep()
+        .args(["-b", "--strip-bom", "-E", "^(name|version)="])
+        .arg(&bom)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "3:name=grep\n13:version=1\n");
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn strip_bom_only_strips_at_the_start_of_each_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let (a, b) = (dir.path().join("a.txt"), dir.path().join("b.txt"));
+    fs::copy(fixture("bom.txt"), &a).unwrap();
+    fs::copy(fixture("bom.txt"), &b).unwrap();
+
+    // The first line of `b.txt` is line 3 across the files.
+    let output = simple_grep()
+        .args(["-n", "--strip-bom", "--continuous-lineno", "-E", "^name"])
+        .arg(&a)
+        .arg(&b)
+        .output()
+        .unwrap();
+    assert_eq!(
+        stdout(&output),
+        format!("{}:1:name=grep\n{}:3:name=grep\n", a.display(), b.display())
+    );
+
+    // A U+FEFF after the start of the file isn't a byte order mark.
+    let path = dir.path().join("middle.txt");
+    fs::write(&path, "abcd\n\u{feff}name=grep\n").unwrap();
+
+    let grep = |pattern: &str| {
+        let output = simple_grep()
+            .args(["-b", "--strip-bom", "--seek-bytes", "5", "-E", pattern])
+            .arg(&path)
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+    assert_eq!(grep("^name"), "");
+    assert_eq!(grep("name"), "5:\u{feff}name=grep\n");
+}
+
+#[test]
+fn quiet_errors_skips_unreadable_files_with_status_2() {
+    let dir = tempfile::tempdir().unwrap();
+    let missing = dir.path().join("missing.log");
+
+    let output = simple_grep()
+        .args(["ERROR"])
+        .arg(&missing)
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert!(!output.stderr.is_empty());
+
+    let output = simple_grep()
+        .args(["--quiet-errors", "refused"])
+        .arg(&missing)
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stderr.is_empty());
+    assert_eq!(
+        stdout(&output),
+        format!(
+            "{}:ERROR: connection refused\n",
+            fixture("app.log").display()
+        )
+    );
+}
+
+#[test]
+fn fail_fast_stops_at_the_first_file_that_cannot_be_read() {
+    // The files are searched in order of their names.
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a.txt"), "valid\n").unwrap();
+    fs::copy(fixture("invalid_utf8.txt"), dir.path().join("b.txt")).unwrap();
+    fs::write(dir.path().join("c.txt"), "valid\n").unwrap();
+
+    let search = |args: &[&str]| {
+        simple_grep()
+            .args(args)
+            .args(["-r", "-c", "valid"])
+            .arg(dir.path())
+            .output()
+            .unwrap()
+    };
+
+    let output = search(&[]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&output).lines().count(), 2);
+
+    let output = search(&["--fail-fast", "--quiet-errors"]);
+    assert!(!output.status.success());
+    let stdout = stdout(&output);
+    assert!(stdout.contains("a.txt:1"), "{stdout}");
+    assert!(!stdout.contains("c.txt"), "{stdout}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.starts_with("Application error: ")
+            && stderr.contains("b.txt: stream did not contain valid UTF-8"),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn pager_is_bypassed_when_stdout_is_not_a_terminal() {
+    // The output of the test is a pipe, so nothing goes through the pager.
+    let output = simple_grep()
+        .args(["--pager", "sed s/^/paged:/", "WARN"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "WARN: retrying\n");
+    assert!(output.stderr.is_empty());
+}
+
+#[test]
+fn no_trailing_newline_leaves_out_the_last_line_break() {
+    let output = simple_grep()
+        .args(["--no-trailing-newline", "ERROR"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "ERROR: disk full\nERROR: connection refused\nERROR: disk full\nERROR: disk full"
+    );
+}
+
+#[test]
+fn append_accumulates_output_across_runs() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("out.txt");
+
+    for query in ["WARN", "INFO"] {
+        let output = simple_grep()
+            .args(["--append", "--output"])
+            .arg(&path)
+            .arg(query)
+            .arg(fixture("app.log"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        assert!(output.stdout.is_empty());
+    }
+
+    assert_eq!(
+        fs::read_to_string(&path).unwrap(),
+        "WARN: retrying\nINFO: service started\nINFO: cleanup\n"
+    );
+
+    let output = simple_grep()
+        .arg("--output")
+        .arg(&path)
+        .arg("WARN")
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "WARN: retrying\n");
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn decompress_stdin_searches_gzipped_standard_input() {
+    let grep = |flags: &[&str], input: &[u8]| {
+        let mut child = simple_grep()
+            .args(flags)
+            .args(["-n", "-"])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap();
+
+        child.stdin.take().unwrap().write_all(input).unwrap();
+        child.wait_with_output().unwrap()
+    };
+
+    let compressed = fs::read(fixture("languages.txt.gz")).unwrap();
+    let expected = simple_grep()
+        .args(["-n", "Rust"])
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+    assert!(!stdout(&expected).is_empty());
+
+    let output = grep(&["-z", "Rust"], &compressed);
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(stdout(&output), stdout(&expected));
+
+    // Without `-z`, the compressed bytes aren't valid UTF-8.
+    let output = grep(&["Rust"], &compressed);
+    assert_eq!(stdout(&output), "");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.starts_with("Application error: "), "{stderr}");
+
+    // Text that only starts like a bzip2 stream is searched as it is.
+    let output = grep(&["-z", "word"], &fs::read(fixture("bzh.txt")).unwrap());
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty());
+    assert_eq!(stdout(&output), "1:BZh is a word\n");
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn compress_writes_gzip_output() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("results.gz");
+
+    for query in ["WARN", "INFO"] {
+        let output = simple_grep()
+            .args(["--compress", "--append", "--output"])
+            .arg(&path)
+            .arg(query)
+            .arg(fixture("app.log"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+    }
+
+    let compressed = fs::read(&path).unwrap();
+    assert!(compressed.starts_with(&[0x1f, 0x8b]));
+
+    // Searching decompresses the output again, and every line has a colon.
+    let output = simple_grep().arg(":").arg(&path).output().unwrap();
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "WARN: retrying\nINFO: service started\nINFO: cleanup\n"
+    );
+}
+
+#[test]
+fn split_output_writes_a_file_per_input() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+    fs::write(dir.path().join("c.txt"), "hay\n").unwrap();
+    let out = dir.path().join("out");
+
+    let output = simple_grep()
+        .current_dir(dir.path())
+        .args(["-n", "--split-output", "out", "--skip-empty", "needle"])
+        .args(["a.txt", "sub/b.txt", "c.txt"])
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        fs::read_to_string(out.join("a.txt.matches")).unwrap(),
+        "a.txt:1:needle in a\n"
+    );
+    assert_eq!(
+        fs::read_to_string(out.join("sub/b.txt.matches")).unwrap(),
+        "sub/b.txt:2:needle in b\n"
+    );
+    assert!(!out.join("c.txt.matches").exists());
+
+    simple_grep()
+        .current_dir(dir.path())
+        .args(["--split-output", "out", "needle", "c.txt"])
+        .output()
+        .unwrap();
+    assert_eq!(fs::read_to_string(out.join("c.txt.matches")).unwrap(), "");
+}
+
+#[test]
+fn split_by_pattern_writes_a_file_per_pattern() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(
+        dir.path().join("app.log"),
+        "ERROR: disk full\nINFO: started\nWARN: disk slow\nERROR: timeout\n",
+    )
+    .unwrap();
+    let out = dir.path().join("out");
+
+    let output = simple_grep()
+        .current_dir(dir.path())
+        .args([
+            "-n",
+            "--split-by-pattern",
+            "out",
+            "-e",
+            "ERROR",
+            "-e",
+            "disk",
+        ])
+        .arg("app.log")
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        fs::read_to_string(out.join("1.txt")).unwrap(),
+        "1:ERROR: disk full\n4:ERROR: timeout\n"
+    );
+    assert_eq!(
+        fs::read_to_string(out.join("2.txt")).unwrap(),
+        "1:ERROR: disk full\n3:WARN: disk slow\n"
+    );
+    assert!(!out.join("3.txt").exists());
+}
+
+#[test]
+fn length_histogram_buckets_selected_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("lengths.txt");
+    let lines = [
+        "x".repeat(3),
+        "x".repeat(5),
+        "y".repeat(7),
+        "x".repeat(12),
+        "x".repeat(25),
+    ];
+    fs::write(&path, lines.join("\n")).unwrap();
+
+    let output = simple_grep()
+        .args(["--length-histogram", "x"])
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    let (half, full) = ("#".repeat(20), "#".repeat(40));
+    assert_eq!(
+        stdout(&output),
+        format!("  0-9 | {full} 2\n10-19 | {half} 1\n20-29 | {half} 1\n")
+    );
+
+    let output = simple_grep()
+        .args(["--length-histogram", "--bucket-size", "5", "x"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    let rows = stdout(&output);
+    let counts: Vec<&str> = rows
+        .lines()
+        .map(|row| row.rsplit(' ').next().unwrap())
+        .collect();
+    assert_eq!(counts, ["1", "1", "1", "0", "0", "1"]);
+    assert_eq!(rows.lines().nth(3), Some("15-19 | 0"));
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_input_matches_string_values_only() {
+    let events = fixture("events.jsonl");
+
+    let output = simple_grep()
+        .args(["-n", "--json-input", "error"])
+        .arg(&events)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        "1:{\"level\":\"error\",\"msg\":\"disk full\"}\n\
+         4:{\"level\":\"info\",\"tags\":[\"error-free\"],\"count\":3}\n"
+    );
+
+    let output = simple_grep()
+        .args(["-n", "--json-input", "--match-invalid-json", "error"])
+        .arg(&events)
+        .output()
+        .unwrap();
+    let lines = stdout(&output);
+    let numbers: Vec<&str> = lines
+        .lines()
+        .map(|line| line.split(':').next().unwrap())
+        .collect();
+    assert_eq!(numbers, ["1", "3", "4"]);
+}
+
+#[cfg(feature = "json")]
+#[test]
+fn json_input_ignores_keys_for_every_pattern() {
+    let grep = |flags: &[&str]| {
+        let output = simple_grep()
+            .args(["--json-input"])
+            .args(flags)
+            .arg(fixture("events.jsonl"))
+            .output()
+            .unwrap();
+        assert!(output.status.success());
+        stdout(&output)
+    };
+
+    // `msg` and `level` are only keys.
+    assert_eq!(
+        grep(&["--count-by-pattern", "-e", "disk", "-e", "msg"]),
+        "disk: 1, msg: 0\n"
+    );
+    assert_eq!(
+        grep(&["--label-patterns", "-e", "disk", "-e", "msg"]),
+        "[disk] {\"level\":\"error\",\"msg\":\"disk full\"}\n"
+    );
+    assert_eq!(grep(&["--also-matching", "level", "disk"]), "");
+    assert_eq!(
+        grep(&["--exclude-matching", "msg", "disk"]),
+        "{\"level\":\"error\",\"msg\":\"disk full\"}\n"
+    );
+}
+
+#[test]
+fn first_only_prints_position_of_first_match() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("first.txt");
+    fs::write(&path, "ok\nhéllo wörld: ERROR one\nERROR two\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--first-only", "ERROR"])
+        .arg(&path)
+        .arg(fixture("languages.txt"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), format!("{}:2:14\n", path.display()));
+}
+
+#[test]
+fn numbers_only_prints_line_numbers_per_file() {
+    let log = fixture("app.log");
+
+    let output = simple_grep()
+        .args(["--numbers-only", "disk"])
+        .arg(&log)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "2,5,7\n");
+
+    let output = simple_grep()
+        .args(["--numbers-only", "-i", "info"])
+        .arg(&log)
+        .arg(fixture("languages.txt"))
+        .arg(&log)
+        .output()
+        .unwrap();
+    assert_eq!(
+        stdout(&output),
+        format!("{0}:1,6\n{0}:1,6\n", log.display())
+    );
+}
+
+#[test]
+fn gaps_reports_the_distances_between_selected_lines() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("activity.log");
+    let lines: Vec<&str> = (1..=12)
+        .map(|number| match number {
+            1 | 3 | 4 | 12 => "active",
+            _ => "idle",
+        })
+        .collect();
+    fs::write(&path, lines.join("\n")).unwrap();
+    let single = dir.path().join("single.log");
+    fs::write(&single, "idle\nactive\n").unwrap();
+
+    let output = simple_grep()
+        .args(["--gaps", "active"])
+        .arg(&path)
+        .arg(&single)
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        stdout(&output),
+        format!("{}: gaps 2,1,8 (max 8)\n", path.display())
+    );
+}
+
+#[test]
+fn respect_crlf_preserves_line_endings() {
+    let crlf = fixture("crlf.txt");
+
+    let output = simple_grep().args(["ERROR"]).arg(&crlf).output().unwrap();
+    assert_eq!(stdout(&output), "ERROR: disk full\n");
+
+    let output = simple_grep()
+        .args(["--respect-crlf", "ERROR"])
+        .arg(&crlf)
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "ERROR: disk full\r\n");
+
+    let output = simple_grep()
+        .args(["--respect-crlf", "--passthru", "ERROR"])
+        .arg(&crlf)
+        .output()
+        .unwrap();
+    assert_eq!(output.stdout, fs::read(&crlf).unwrap());
+}
+
+#[test]
+fn offset_relative_counts_from_the_line_start() {
+    let output = simple_grep()
+        .args(["-o", "-b", "refused"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert_eq!(stdout(&output), "72:refused\n");
+
+    let output = simple_grep()
+        .args(["-o", "-b", "--offset-relative", "refused"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "18:refused\n");
+}
+
+#[cfg(unix)]
+#[test]
+fn exclude_dir_prunes_matching_directories() {
+    let dir = tempfile::tempdir().unwrap();
+    create_tree(dir.path());
+    fs::create_dir_all(dir.path().join("node_modules/pkg")).unwrap();
+    fs::write(dir.path().join("node_modules/pkg/c.txt"), "needle in c\n").unwrap();
+    // Walking into the excluded directory would report this loop.
+    std::os::unix::fs::symlink(dir.path(), dir.path().join("node_modules/loop")).unwrap();
+
+    let output = simple_grep()
+        .args([
+            "-r",
+            "--follow",
+            "--exclude-dir",
+            "node_*",
+            "--exclude-dir",
+            "s?b",
+        ])
+        .args(["needle"])
+        .arg(dir.path())
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(
+        sorted_lines(&output),
+        [format!(
+            "{}:needle in a",
+            dir.path().join("a.txt").display()
+        )]
+    );
+    assert!(output.stderr.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn pre_searches_the_output_of_a_command() {
+    let output = simple_grep()
+        .args(["--pre", "cat", "-n", "refused"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "4:ERROR: connection refused\n");
+
+    let output = simple_grep()
+        .args(["--pre", "false", "refused"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("warning"), "{stderr}");
+    assert!(stderr.contains("'false' failed"), "{stderr}");
+}
+
+#[cfg(unix)]
+#[test]
+fn pre_command_takes_arguments_and_reads_standard_input() {
+    let output = simple_grep()
+        .args(["--pre", "sed s/refused/reset/", "-n", "reset"])
+        .arg(fixture("app.log"))
+        .output()
+        .unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "4:ERROR: connection reset\n");
+
+    let mut child = simple_grep()
+        .args(["--pre", "tr a-z A-Z", "NEEDLE", "-"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"needle\nhay\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(stdout(&output), "NEEDLE\n");
+}
@@ -1,4 +1,14 @@
-use simple_grep::{search, search_case_insensitive};
+use std::cell::RefCell;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::rc::Rc;
+use std::thread;
+use std::time::{Duration, Instant, UNIX_EPOCH};
+
+use simple_grep::{
+    CancelFlag, ClipboardSource, Clock, Config, FuzzyMatcher, Matcher, RetryReader, Template,
+    clipboard_query, group_count, help, list_options, long_path, open_maybe_compressed, search,
+    search_ascii_case_insensitive, search_case_insensitive, search_reader,
+};
 
 #[test]
 fn case_sensitive() {
@@ -26,3 +36,631 @@ Trust me.";
         search_case_insensitive(query, contents)
     );
 }
+
+#[test]
+fn ascii_case_insensitive() {
+    let query = "rUsT";
+    let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+    assert_eq!(
+        vec!["Rust:", "Trust me."],
+        search_ascii_case_insensitive(query, contents)
+    );
+}
+
+#[test]
+fn ascii_case_insensitive_skips_non_ascii_folding() {
+    let query = "straße";
+    let contents = "\
+STRAßE
+STRASSE
+Straße";
+
+    assert_eq!(
+        vec!["STRAßE", "Straße"],
+        search_ascii_case_insensitive(query, contents)
+    );
+    assert!(search_ascii_case_insensitive("été", "ÉTÉ").is_empty());
+    assert_eq!(vec!["ÉTÉ"], search_case_insensitive("été", "ÉTÉ"));
+}
+
+#[test]
+fn group_count_orders_by_frequency_then_line() {
+    let contents = "\
+ERROR: timeout
+INFO: started
+ERROR: disk full
+ERROR: timeout
+ERROR: disk full
+ERROR: auth";
+
+    assert_eq!(
+        vec![
+            (2, "ERROR: disk full"),
+            (2, "ERROR: timeout"),
+            (1, "ERROR: auth"),
+        ],
+        group_count(search("ERROR", contents))
+    );
+}
+
+fn read_fixture(name: &str) -> String {
+    let path = format!("{}/tests/fixtures/{name}", env!("CARGO_MANIFEST_DIR"));
+    let mut contents = String::new();
+    open_maybe_compressed(path)
+        .unwrap()
+        .read_to_string(&mut contents)
+        .unwrap();
+    contents
+}
+
+#[test]
+fn plain_input_is_read_as_is() {
+    let contents = read_fixture("languages.txt");
+
+    assert_eq!(
+        vec!["Go is expressive, concise, clean, and efficient."],
+        search("Go", &contents)
+    );
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn gzip_input_is_decompressed() {
+    let contents = read_fixture("languages.txt.gz");
+
+    assert_eq!(
+        vec!["Go is expressive, concise, clean, and efficient."],
+        search("Go", &contents)
+    );
+}
+
+#[cfg(feature = "bzip2")]
+#[test]
+fn bzip2_input_is_decompressed() {
+    let contents = read_fixture("languages.txt.bz2");
+
+    assert_eq!(
+        vec!["Go is expressive, concise, clean, and efficient."],
+        search("Go", &contents)
+    );
+}
+
+#[cfg(feature = "xz")]
+#[test]
+fn xz_input_is_decompressed() {
+    let contents = read_fixture("languages.txt.xz");
+
+    assert_eq!(
+        vec!["Go is expressive, concise, clean, and efficient."],
+        search("Go", &contents)
+    );
+}
+
+#[test]
+fn text_starting_like_bzip2_is_read_as_is() {
+    assert_eq!(read_fixture("bzh.txt"), "BZh is a word\nother\n");
+}
+
+#[cfg(feature = "gzip")]
+#[test]
+fn text_starting_like_gzip_is_read_as_is() {
+    let path = format!(
+        "{}/tests/fixtures/gzip_magic.txt",
+        env!("CARGO_MANIFEST_DIR")
+    );
+    let mut contents = Vec::new();
+    open_maybe_compressed(&path)
+        .unwrap()
+        .read_to_end(&mut contents)
+        .unwrap();
+
+    assert_eq!(contents, b"\x1f\x8b is not gzip\nother\n");
+}
+
+#[cfg(windows)]
+#[test]
+fn long_path_adds_verbatim_prefix() {
+    use std::path::Path;
+
+    assert_eq!(
+        long_path(Path::new(r"C:\logs\app.log")),
+        Path::new(r"\\?\C:\logs\app.log")
+    );
+    assert_eq!(
+        long_path(Path::new(r"\\server\share\app.log")),
+        Path::new(r"\\?\UNC\server\share\app.log")
+    );
+    assert_eq!(
+        long_path(Path::new(r"\\?\C:\logs\app.log")),
+        Path::new(r"\\?\C:\logs\app.log")
+    );
+    let relative = long_path(Path::new("app.log"));
+    assert!(relative.to_str().unwrap().starts_with(r"\\?\"));
+}
+
+#[cfg(not(windows))]
+#[test]
+fn long_path_is_a_no_op() {
+    use std::path::Path;
+
+    for path in [
+        "logs/app.log",
+        "/var/log/app.log",
+        r"\\server\share\app.log",
+    ] {
+        assert_eq!(long_path(Path::new(path)), Path::new(path));
+    }
+}
+
+#[test]
+fn template_parsing() {
+    assert!("{path}|{lineno}|{text}|{count}".parse::<Template>().is_ok());
+    assert!("{{literal}} {text}".parse::<Template>().is_ok());
+    assert!("{line}".parse::<Template>().is_err());
+    assert!("{text".parse::<Template>().is_err());
+    assert!("text}".parse::<Template>().is_err());
+}
+
+/// A writer whose output can be inspected while it's still being written to.
+#[derive(Clone, Default)]
+struct SharedOutput(Rc<RefCell<Vec<u8>>>);
+
+impl SharedOutput {
+    fn contents(&self) -> String {
+        String::from_utf8(self.0.borrow().clone()).unwrap()
+    }
+}
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// A reader that hands out one line per read and records what had been
+/// written to `output` before each line was read.
+struct SteppedReader {
+    lines: Vec<&'static str>,
+    output: SharedOutput,
+    seen: Vec<String>,
+}
+
+impl Read for SteppedReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let chunk = self.fill_buf()?;
+        let len = chunk.len().min(buf.len());
+        buf[..len].copy_from_slice(&chunk[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for SteppedReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.lines.first().map_or(&[][..], |line| line.as_bytes()))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if amt > 0 {
+            self.lines.remove(0);
+            self.seen.push(self.output.contents());
+        }
+    }
+}
+
+#[test]
+fn search_reader_writes_matches_as_they_arrive() {
+    let config = Config {
+        query: String::from("ERROR"),
+        ..Config::default()
+    };
+    let mut output = SharedOutput::default();
+    let mut reader = SteppedReader {
+        lines: vec!["ERROR: one\n", "INFO: two\n", "ERROR: three\n"],
+        output: output.clone(),
+        seen: Vec::new(),
+    };
+
+    search_reader(&config, &mut reader, &mut output).unwrap();
+
+    // What had been written at the time each line was consumed.
+    assert_eq!(reader.seen, ["", "ERROR: one\n", "ERROR: one\n"]);
+    assert_eq!(output.contents(), "ERROR: one\nERROR: three\n");
+}
+
+#[test]
+fn fuzzy_matches_exact_occurrences() {
+    let matcher = FuzzyMatcher::new("rust", 2, false);
+
+    assert!(matcher.is_match("I love rust"));
+    assert_eq!(vec![(7, 11)], matcher.find_spans("I love rust"));
+    assert_eq!(vec![(1, 5)], matcher.find_spans("trust"));
+}
+
+#[test]
+fn fuzzy_matches_within_one_edit() {
+    let matcher = FuzzyMatcher::new("receive", 1, false);
+
+    // Substitution, deletion and insertion.
+    assert!(matcher.is_match("please receeve this"));
+    assert!(matcher.is_match("please receve this"));
+    assert!(matcher.is_match("please receivve this"));
+    assert_eq!(vec![(7, 14)], matcher.find_spans("please receeve this"));
+
+    let matcher = FuzzyMatcher::new("RECEIVE", 1, true);
+    assert!(matcher.is_match("please receeve this"));
+}
+
+#[test]
+fn fuzzy_rejects_too_distant_lines() {
+    let matcher = FuzzyMatcher::new("receive", 1, false);
+
+    assert!(!matcher.is_match("please recyeve this"));
+    assert!(!matcher.is_match("RECEIVE"));
+    assert!(matcher.find_spans("nothing like it").is_empty());
+}
+
+fn build(args: &[&str]) -> Result<Config, String> {
+    let args = ["simple_grep"]
+        .iter()
+        .chain(args)
+        .map(|arg| arg.to_string());
+    Config::build(args)
+}
+
+#[test]
+fn buffer_size_accepts_suffixes() {
+    let capacity = |size| {
+        build(&["--buffer-size", size, "q", "f"])
+            .unwrap()
+            .buffer_capacity()
+    };
+
+    assert_eq!(capacity("4096"), 4096);
+    assert_eq!(capacity("64K"), 64 * 1024);
+    assert_eq!(capacity("1m"), 1024 * 1024);
+    assert_eq!(build(&["q", "f"]).unwrap().buffer_capacity(), 8 * 1024);
+}
+
+#[test]
+fn buffer_size_rejects_nonsensical_sizes() {
+    for size in ["0", "0K", "K", "-1", "12X", "99999999999999999999G"] {
+        assert!(build(&["--buffer-size", size, "q", "f"]).is_err(), "{size}");
+    }
+
+    // Sizes too large to allocate are rejected before reading anything.
+    assert_eq!(
+        build(&["--buffer-size", "100G", "q", "f"]).err().unwrap(),
+        "Invalid value '100G' for '--buffer-size': size must be at most 1G"
+    );
+    assert!(build(&["--buffer-size", "1G", "q", "f"]).is_ok());
+}
+
+#[test]
+fn every_option_in_help_is_accepted() {
+    for line in help().lines().filter(|line| line.starts_with("  ")) {
+        let mut words = line
+            .split_whitespace()
+            .skip_while(|word| !word.starts_with("--"));
+        let name = words.next().unwrap();
+        let name = name.split('[').next().unwrap();
+
+        let mut args = vec![name];
+        match words.next() {
+            Some("FORM") => args.push("NFC"),
+            Some("ENCODING") => args.push("utf-8"),
+            Some(word) if word.chars().all(|c| c.is_ascii_uppercase()) => args.push("10"),
+            _ => {}
+        }
+        args.extend(["q", "f"]);
+
+        assert!(build(&args).is_ok(), "{line}");
+    }
+}
+
+#[test]
+fn many_patterns_select_the_lines_matching_any_of_them() {
+    let patterns: Vec<String> = (0..40).map(|i| format!("user:{}", i * 7)).collect();
+    let contents: String = (0..500)
+        .map(|i| {
+            let key = if i % 2 == 0 { "user" } else { "USER" };
+            format!("2024-01-02 WARN cache miss for key {key}:{i}\n")
+        })
+        .collect();
+
+    for ignore_case in [false, true] {
+        let config = Config {
+            patterns: patterns.clone(),
+            ignore_case,
+            ascii_case: ignore_case,
+            ..Config::default()
+        };
+        let mut out = Vec::new();
+        search_reader(&config, contents.as_bytes(), &mut out).unwrap();
+
+        // Checking each pattern in turn, as is done for a few patterns.
+        let expected: String = contents
+            .lines()
+            .filter(|line| {
+                patterns.iter().any(|pattern| {
+                    if ignore_case {
+                        line.to_ascii_lowercase().contains(pattern.as_str())
+                    } else {
+                        line.contains(pattern.as_str())
+                    }
+                })
+            })
+            .map(|line| format!("{line}\n"))
+            .collect();
+
+        assert!(!expected.is_empty());
+        assert_eq!(String::from_utf8(out).unwrap(), expected, "{ignore_case}");
+    }
+}
+
+#[test]
+fn list_options_describes_every_option_of_help() {
+    let options = list_options();
+    let fields: Vec<Vec<&str>> = options
+        .lines()
+        .map(|line| line.split('\t').collect())
+        .collect();
+
+    assert!(fields.iter().all(|fields| fields.len() == 4));
+    assert_eq!(
+        fields.len(),
+        help().lines().filter(|line| line.starts_with("  ")).count()
+    );
+    assert!(fields.contains(&vec![
+        "--context",
+        "-C",
+        "yes",
+        "Print N lines before and after each selected line"
+    ]));
+    assert!(
+        fields
+            .iter()
+            .any(|fields| fields[..3] == ["--count", "-c", "no"])
+    );
+    assert!(
+        fields
+            .iter()
+            .any(|fields| fields[..3] == ["--color", "", "optional"])
+    );
+}
+
+#[cfg(feature = "regex")]
+#[test]
+fn regex_skips_empty_matches() {
+    use simple_grep::RegexMatcher;
+
+    let matcher = RegexMatcher::new("a*", false).unwrap();
+
+    assert!(matcher.is_match("xyz"));
+    assert!(matcher.find_spans("xyz").is_empty());
+    assert_eq!(vec![(1, 4), (5, 6)], matcher.find_spans("baaaca"));
+    assert_eq!(vec![(4, 5)], matcher.find_spans("ééa"));
+
+    let matcher = RegexMatcher::new(r"\b", false).unwrap();
+    assert!(matcher.find_spans("two words").is_empty());
+}
+
+/// A reader that fails with `Interrupted` a few times before each read
+/// succeeds.
+struct InterruptedReader<'a> {
+    data: &'a [u8],
+    interruptions: usize,
+    remaining: usize,
+}
+
+impl Read for InterruptedReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.remaining > 0 {
+            self.remaining -= 1;
+            return Err(io::Error::from(io::ErrorKind::Interrupted));
+        }
+
+        self.remaining = self.interruptions;
+        self.data.read(buf)
+    }
+}
+
+#[test]
+fn retry_reader_retries_interrupted_reads() {
+    let interrupted = || InterruptedReader {
+        data: b"ERROR: one\nINFO: two\n",
+        interruptions: 3,
+        remaining: 3,
+    };
+
+    let mut buf = [0; 6];
+    assert_eq!(
+        interrupted().read(&mut buf).unwrap_err().kind(),
+        io::ErrorKind::Interrupted
+    );
+
+    let mut reader = RetryReader::new(interrupted());
+    assert_eq!(reader.read(&mut buf).unwrap(), 6);
+    assert_eq!(&buf, b"ERROR:");
+
+    let mut reader = io::BufReader::new(RetryReader::new(interrupted()));
+    assert_eq!(reader.fill_buf().unwrap(), b"ERROR: one\nINFO: two\n");
+}
+
+/// A reader that hands out one line per read and sets `cancel` once
+/// `cancel_after` lines have been read, like Ctrl-C during a search.
+struct CancellingReader {
+    lines: Vec<&'static str>,
+    cancel_after: usize,
+    cancel: CancelFlag,
+}
+
+impl Read for CancellingReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let chunk = self.fill_buf()?;
+        let len = chunk.len().min(buf.len());
+        buf[..len].copy_from_slice(&chunk[..len]);
+        self.consume(len);
+        Ok(len)
+    }
+}
+
+impl BufRead for CancellingReader {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        Ok(self.lines.first().map_or(&[][..], |line| line.as_bytes()))
+    }
+
+    fn consume(&mut self, amt: usize) {
+        if amt > 0 {
+            self.lines.remove(0);
+            self.cancel_after -= 1;
+
+            if self.cancel_after == 0 {
+                self.cancel.cancel();
+            }
+        }
+    }
+}
+
+#[test]
+fn cancelled_search_stops_after_the_current_line() {
+    let config = Config {
+        query: String::from("ERROR"),
+        count: true,
+        ..Config::default()
+    };
+    let mut reader = CancellingReader {
+        lines: vec![
+            "ERROR: one\n",
+            "ERROR: two\n",
+            "ERROR: three\n",
+            "ERROR: four\n",
+        ],
+        cancel_after: 2,
+        cancel: config.cancel.clone(),
+    };
+    let mut out = Vec::new();
+
+    search_reader(&config, &mut reader, &mut out).unwrap();
+
+    assert!(config.cancel.is_cancelled());
+    assert_eq!(reader.lines.len(), 2);
+    assert_eq!(String::from_utf8(out).unwrap(), "2\n");
+}
+
+/// A reader that takes `delay` to produce each line, like a slow network
+/// mount.
+struct SlowReader {
+    lines: Vec<&'static str>,
+    delay: Duration,
+}
+
+impl Read for SlowReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.lines.is_empty() {
+            return Ok(0);
+        }
+
+        thread::sleep(self.delay);
+        let line = self.lines.remove(0).as_bytes();
+        buf[..line.len()].copy_from_slice(line);
+        Ok(line.len())
+    }
+}
+
+#[test]
+fn deadline_stops_a_slow_search_early() {
+    let config = Config {
+        query: String::from("ERROR"),
+        ..Config::default()
+    };
+    let reader = SlowReader {
+        lines: vec!["ERROR: slow\n"; 100],
+        delay: Duration::from_millis(20),
+    };
+    let mut out = Vec::new();
+
+    let start = Instant::now();
+    let deadline = config.cancel.cancel_after(Duration::from_millis(100));
+    search_reader(&config, BufReader::new(reader), &mut out).unwrap();
+
+    // Reading all lines would take 2 seconds.
+    assert!(start.elapsed() < Duration::from_secs(1));
+    assert!(deadline.expired());
+    let lines = String::from_utf8(out).unwrap().lines().count();
+    assert!((1..100).contains(&lines), "{lines}");
+}
+
+/// A clipboard holding `text`, or failing to be read if it's `None`.
+struct MockClipboard {
+    text: Option<&'static str>,
+}
+
+impl ClipboardSource for MockClipboard {
+    fn text(&mut self) -> Result<String, String> {
+        self.text
+            .map(String::from)
+            .ok_or_else(|| String::from("no clipboard"))
+    }
+}
+
+#[test]
+fn pattern_clipboard_takes_the_query_from_the_clipboard() {
+    let args = ["simple_grep", "--pattern-clipboard", "-n", "app.log"];
+    let mut config = Config::build(args.into_iter().map(String::from)).unwrap();
+
+    assert!(config.pattern_clipboard);
+    assert_eq!(config.query, "");
+    assert_eq!(config.file_paths, ["app.log"]);
+
+    let mut clipboard = MockClipboard {
+        text: Some("disk full\n"),
+    };
+    config.query = clipboard_query(&mut clipboard).unwrap();
+    let mut out = Vec::new();
+    search_reader(
+        &config,
+        "ERROR: disk\nERROR: disk full\n".as_bytes(),
+        &mut out,
+    )
+    .unwrap();
+
+    assert_eq!(String::from_utf8(out).unwrap(), "2:ERROR: disk full\n");
+    assert!(clipboard_query(&mut MockClipboard { text: Some("") }).is_err());
+    assert_eq!(
+        clipboard_query(&mut MockClipboard { text: None }),
+        Err(String::from("no clipboard"))
+    );
+}
+
+#[test]
+fn timestamp_prefixes_matches_with_the_time_of_the_clock() {
+    let config = Config {
+        query: String::from("ERROR"),
+        timestamp: true,
+        line_number: true,
+        clock: Clock::fixed(UNIX_EPOCH + Duration::from_secs(1_704_164_645)),
+        ..Config::default()
+    };
+    let mut out = Vec::new();
+    search_reader(&config, "INFO: up\nERROR: down\n".as_bytes(), &mut out).unwrap();
+
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "[2024-01-02T03:04:05Z] 2:ERROR: down\n"
+    );
+
+    let rfc3339 = |seconds| Clock::fixed(UNIX_EPOCH + Duration::from_secs(seconds)).rfc3339();
+    assert_eq!(rfc3339(0), "1970-01-01T00:00:00Z");
+    assert_eq!(rfc3339(1_709_251_199), "2024-02-29T23:59:59Z");
+    assert_eq!(rfc3339(978_264_000), "2000-12-31T12:00:00Z");
+}